@@ -21,7 +21,7 @@ async fn sleep_for_secs(secs: u64) {
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize and open a new browser
-    let mut browser = Browser::new(LOCALHOST, PORT);
+    let browser = Browser::new(LOCALHOST, PORT);
     browser.open().await?;
 
     // Load rust-lang.org
@@ -7,7 +7,7 @@ const PORT: u16 = 4444;
 #[tokio::main]
 async fn main() -> Result<()> {
     // Create a new browser instance
-    let mut browser = Browser::new(LOCALHOST, PORT);
+    let browser = Browser::new(LOCALHOST, PORT);
     browser.open().await?;
 
     // Navigate to a test page
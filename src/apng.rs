@@ -0,0 +1,221 @@
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32(bytes: &[&[u8]]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes.iter().flat_map(|part| part.iter()) {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xffff_ffff
+}
+
+// --------------------------------------------------
+
+struct Chunk<'a> {
+    kind: &'a [u8],
+    data: &'a [u8],
+}
+
+/// Splits a PNG into its chunks, up to and including `IEND`.
+fn parse_chunks(png: &[u8]) -> Option<Vec<Chunk<'_>>> {
+    if png.get(..8)? != SIGNATURE {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    loop {
+        let length = u32::from_be_bytes(png.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let kind = png.get(pos + 4..pos + 8)?;
+        let data = png.get(pos + 8..pos + 8 + length)?;
+        pos += 12 + length;
+        chunks.push(Chunk { kind, data });
+        if kind == b"IEND" {
+            return Some(chunks);
+        }
+    }
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(&[kind, data]).to_be_bytes());
+}
+
+fn header<'a>(chunks: &[Chunk<'a>]) -> Option<&'a [u8]> {
+    chunks
+        .iter()
+        .find(|chunk| chunk.kind == b"IHDR")
+        .map(|chunk| chunk.data)
+        .filter(|data| data.len() == 13)
+}
+
+// --------------------------------------------------
+
+/// Encodes PNG frames into an APNG looping forever, showing each frame for `delay_ms`.
+/// The compressed image data of the frames is reused as-is.
+///
+/// Frames that are not valid PNGs, or whose size or color format differs from the
+/// first frame, are skipped. Returns `None` if there is no usable frame.
+pub fn encode(frames: &[Vec<u8>], delay_ms: u64) -> Option<Vec<u8>> {
+    let mut parsed = frames.iter().filter_map(|frame| parse_chunks(frame));
+    let first = parsed.next()?;
+    let first_header = header(&first)?;
+    let mut frames = vec![first];
+    frames.extend(parsed.filter(|chunks| header(chunks) == Some(first_header)));
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", first_header);
+    let mut animation_control = (frames.len() as u32).to_be_bytes().to_vec();
+    animation_control.extend_from_slice(&0u32.to_be_bytes());
+    write_chunk(&mut out, b"acTL", &animation_control);
+
+    // Ancillary chunks of the first frame, such as color profiles, apply to the whole animation
+    for chunk in frames[0].iter().take_while(|chunk| chunk.kind != b"IDAT") {
+        if !matches!(chunk.kind, b"IHDR" | b"acTL" | b"fcTL" | b"fdAT") {
+            write_chunk(&mut out, chunk.kind, chunk.data);
+        }
+    }
+
+    let delay = delay_ms.min(u16::MAX as u64) as u16;
+    let mut sequence = 0u32;
+    for (index, chunks) in frames.iter().enumerate() {
+        let mut frame_control = sequence.to_be_bytes().to_vec();
+        frame_control.extend_from_slice(&first_header[0..8]); // width and height
+        frame_control.extend_from_slice(&[0; 8]); // x and y offsets
+        frame_control.extend_from_slice(&delay.to_be_bytes());
+        frame_control.extend_from_slice(&1000u16.to_be_bytes());
+        frame_control.extend_from_slice(&[0, 0]); // dispose and blend operations
+        write_chunk(&mut out, b"fcTL", &frame_control);
+        sequence += 1;
+
+        for chunk in chunks.iter().filter(|chunk| chunk.kind == b"IDAT") {
+            if index == 0 {
+                write_chunk(&mut out, b"IDAT", chunk.data);
+            } else {
+                let mut frame_data = sequence.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(chunk.data);
+                write_chunk(&mut out, b"fdAT", &frame_data);
+                sequence += 1;
+            }
+        }
+    }
+    write_chunk(&mut out, b"IEND", &[]);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PNG from its IHDR data and IDAT payloads; the image data isn't decoded.
+    fn png(width: u32, height: u32, idats: &[&[u8]]) -> Vec<u8> {
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, 6, 0, 0, 0]); // 8-bit RGBA
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", &header);
+        for idat in idats {
+            write_chunk(&mut out, b"IDAT", idat);
+        }
+        write_chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    /// Returns the kind, data and stored CRC of each chunk.
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>, u32)> {
+        let mut chunks = Vec::new();
+        let mut pos = 8;
+        while pos < png.len() {
+            let length = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let kind = String::from_utf8(png[pos + 4..pos + 8].to_vec()).unwrap();
+            let data = png[pos + 8..pos + 8 + length].to_vec();
+            let crc = u32::from_be_bytes(png[pos + 8 + length..pos + 12 + length].try_into().unwrap());
+            chunks.push((kind, data, crc));
+            pos += 12 + length;
+        }
+        chunks
+    }
+
+    fn be32(data: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn crc_matches_known_values() {
+        assert_eq!(crc32(&[b"IEND"]), 0xae42_6082);
+        assert_eq!(crc32(&[b"123456789"]), 0xcbf4_3926);
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn encodes_two_frames() {
+        let frames = [png(2, 3, &[b"first"]), png(2, 3, &[b"second-a", b"second-b"])];
+        let apng = encode(&frames, 250).unwrap();
+        assert_eq!(apng[..8], SIGNATURE);
+
+        let chunks = chunks(&apng);
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _, _)| kind.as_str()).collect();
+        assert_eq!(kinds, ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "fdAT", "IEND"]);
+        for (kind, data, crc) in &chunks {
+            assert_eq!(*crc, crc32(&[kind.as_bytes(), data]), "CRC of {}", kind);
+        }
+
+        // Two frames, looping forever
+        let animation_control = &chunks[1].1;
+        assert_eq!((be32(animation_control, 0), be32(animation_control, 4)), (2, 0));
+
+        // Frame controls: sequence number, size, offsets, 250/1000 s delay
+        let first_control = &chunks[2].1;
+        assert_eq!(first_control.len(), 26);
+        assert_eq!(be32(first_control, 0), 0);
+        assert_eq!((be32(first_control, 4), be32(first_control, 8)), (2, 3));
+        assert_eq!((be32(first_control, 12), be32(first_control, 16)), (0, 0));
+        assert_eq!(first_control[20..24], [0, 250, 0x03, 0xe8]);
+        assert_eq!(chunks[3].1, b"first");
+
+        // Later frames continue the sequence, their data prefixed with it
+        assert_eq!(be32(&chunks[4].1, 0), 1);
+        assert_eq!(be32(&chunks[5].1, 0), 2);
+        assert_eq!(&chunks[5].1[4..], b"second-a");
+        assert_eq!(be32(&chunks[6].1, 0), 3);
+        assert_eq!(&chunks[6].1[4..], b"second-b");
+    }
+
+    #[test]
+    fn skips_invalid_and_mismatched_frames() {
+        let frames = [
+            b"not a png".to_vec(),
+            png(2, 3, &[b"first"]),
+            png(4, 3, &[b"larger"]),
+        ];
+        let apng = encode(&frames, 100).unwrap();
+        let chunks = chunks(&apng);
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _, _)| kind.as_str()).collect();
+        assert_eq!(kinds, ["IHDR", "acTL", "fcTL", "IDAT", "IEND"]);
+        assert_eq!(be32(&chunks[1].1, 0), 1);
+    }
+
+    #[test]
+    fn no_usable_frame_is_an_error() {
+        assert!(encode(&[], 100).is_none());
+        assert!(encode(&[b"not a png".to_vec()], 100).is_none());
+    }
+}
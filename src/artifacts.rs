@@ -0,0 +1,331 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, error};
+use serde_json::Value;
+use webdriverbidi::model::browsing_context::GetTreeParameters;
+use webdriverbidi::model::session::SubscriptionRequest;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::error::BrowserError;
+use crate::events::EventDispatcher;
+use crate::screenshot::{self, ScreenshotOptions};
+use crate::script;
+
+// --------------------------------------------------
+
+const LOG_ENTRY_ADDED: &str = "log.entryAdded";
+
+/// How long capturing each artifact may take, so that a hung browser doesn't stall the
+/// failing test.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// --------------------------------------------------
+
+/// What a `Browser` saves when one of its actions fails, and where.
+///
+/// Each failure gets a directory of its own, named after the time and the action, e.g.
+/// `target/artifacts/20261018-142501-042-click_element`, holding:
+/// - `error.txt`: the action and the error.
+/// - `locator.txt`: the failing selector, for actions taking one.
+/// - `url.txt`: the URL of the current page.
+/// - `page.html`: the HTML of the current page.
+/// - `screenshot.png`: a screenshot of the whole page.
+/// - `console.log`: the last console messages and JavaScript errors.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::ArtifactPolicy;
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// browser
+///     .set_artifact_policy(Some(ArtifactPolicy::new("target/artifacts").console_lines(100)))
+///     .await?;
+/// if let Err(e) = browser.click_element("#checkout").await {
+///     if let Some(dir) = e.artifacts() {
+///         println!("Failure artifacts saved to {}", dir.display());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactPolicy {
+    dir: PathBuf,
+    screenshot: bool,
+    html: bool,
+    console_lines: usize,
+}
+
+impl ArtifactPolicy {
+    /// Creates a policy saving every artifact, with the last 50 console entries, into
+    /// directories created under `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            screenshot: true,
+            html: true,
+            console_lines: 50,
+        }
+    }
+
+    /// Sets whether a screenshot of the page is saved.
+    pub fn screenshot(mut self, screenshot: bool) -> Self {
+        self.screenshot = screenshot;
+        self
+    }
+
+    /// Sets whether the HTML of the page is saved.
+    pub fn html(mut self, html: bool) -> Self {
+        self.html = html;
+        self
+    }
+
+    /// Sets how many of the last console entries are saved, `0` to save none.
+    pub fn console_lines(mut self, console_lines: usize) -> Self {
+        self.console_lines = console_lines;
+        self
+    }
+
+    pub(crate) fn console_capacity(&self) -> usize {
+        self.console_lines
+    }
+}
+
+// --------------------------------------------------
+
+/// Keeps the last console messages and JavaScript errors of the session.
+#[derive(Default)]
+pub struct ConsoleLog {
+    entries: Mutex<VecDeque<String>>,
+    capacity: AtomicUsize,
+    listening: AtomicBool,
+}
+
+impl ConsoleLog {
+    fn push(&self, entry: String) {
+        let capacity = self.capacity.load(Ordering::SeqCst);
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.push_back(entry);
+            while entries.len() > capacity {
+                entries.pop_front();
+            }
+        }
+    }
+
+    fn tail(&self) -> Vec<String> {
+        self.entries
+            .lock()
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Stops keeping entries and discards the kept ones.
+    pub fn stop(&self) {
+        self.capacity.store(0, Ordering::SeqCst);
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+}
+
+/// Formats a `log.entryAdded` event as `[level] text`.
+fn console_entry(event: &Value) -> Option<String> {
+    let params = event.get("params")?;
+    let level = params.get("level").and_then(Value::as_str).unwrap_or("info");
+    let text = params.get("text").and_then(Value::as_str).unwrap_or_default();
+    Some(format!("[{}] {}", level, text))
+}
+
+/// Keeps the last `capacity` console entries of the session, subscribing to
+/// `log.entryAdded` the first time.
+pub async fn record_console(
+    session: &mut WebDriverBiDiSession,
+    events: &EventDispatcher,
+    log: &Arc<ConsoleLog>,
+    capacity: usize,
+) -> Result<(), BrowserError> {
+    log.capacity.store(capacity, Ordering::SeqCst);
+    if log.listening.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    debug!("Recording console entries");
+    let handler_log = log.clone();
+    let handler = events.subscribe(LOG_ENTRY_ADDED, move |event| {
+        match console_entry(event) {
+            Some(entry) => handler_log.push(entry),
+            None => error!("Unexpected log entry event: {:?}", event),
+        }
+        true
+    });
+
+    let subscription_params = SubscriptionRequest::new(vec![LOG_ENTRY_ADDED.to_string()], None, None);
+    if let Err(e) = session.session_subscribe(subscription_params).await {
+        events.unsubscribe(handler);
+        log.listening.store(false, Ordering::SeqCst);
+        return Err(BrowserError::command("session.subscribe", e));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+
+/// An action that failed.
+pub struct Failure<'a> {
+    pub action: &'a str,
+    pub locator: Option<&'a str>,
+    pub error: &'a BrowserError,
+}
+
+/// Saves the artifacts of a failed action into a new timestamped directory and returns it.
+///
+/// Artifacts that can't be captured are skipped. Nothing is captured from the browser
+/// when `context` is `None`, e.g. once the connection is lost.
+pub async fn capture(
+    session: &mut WebDriverBiDiSession,
+    context: Option<&str>,
+    sandbox: Option<&str>,
+    policy: &ArtifactPolicy,
+    console: &ConsoleLog,
+    failure: &Failure<'_>,
+) -> io::Result<PathBuf> {
+    let dir = create_dir(&policy.dir, failure.action)?;
+    debug!("Saving the failure artifacts of {} to {}", failure.action, dir.display());
+
+    fs::write(dir.join("error.txt"), format!("{}\n{}\n", failure.action, failure.error))?;
+    if let Some(locator) = failure.locator {
+        fs::write(dir.join("locator.txt"), locator)?;
+    }
+    if policy.console_lines > 0 {
+        let mut lines = console.tail().join("\n");
+        lines.push('\n');
+        fs::write(dir.join("console.log"), lines.trim_start())?;
+    }
+
+    let Some(context) = context else {
+        return Ok(dir);
+    };
+    match with_timeout(current_url(session, context)).await {
+        Ok(url) => fs::write(dir.join("url.txt"), url)?,
+        Err(e) => error!("Capturing the URL failed: {}", e),
+    }
+    if policy.html {
+        let target = script::context_target(context, sandbox);
+        let html = script::evaluate(session, target, "document.documentElement.outerHTML");
+        match with_timeout(html).await {
+            Ok(html) => fs::write(dir.join("page.html"), html.as_str().unwrap_or_default())?,
+            Err(e) => error!("Capturing the page HTML failed: {}", e),
+        }
+    }
+    if policy.screenshot {
+        let options = ScreenshotOptions::default();
+        let data = screenshot::take_screenshot(session, context.to_string(), sandbox, &options);
+        match with_timeout(data).await.and_then(|data| screenshot::decode(&data)) {
+            Ok(bytes) => fs::write(dir.join("screenshot.png"), bytes)?,
+            Err(e) => error!("Capturing the screenshot failed: {}", e),
+        }
+    }
+    Ok(dir)
+}
+
+async fn with_timeout<T>(
+    future: impl Future<Output = Result<T, BrowserError>>,
+) -> Result<T, BrowserError> {
+    tokio::time::timeout(CAPTURE_TIMEOUT, future)
+        .await
+        .unwrap_or_else(|_| Err(BrowserError::timeout("Capturing the artifact", CAPTURE_TIMEOUT)))
+}
+
+async fn current_url(session: &mut WebDriverBiDiSession, context: &str) -> Result<String, BrowserError> {
+    let params = GetTreeParameters::new(Some(0), Some(context.to_string()));
+    let result = session
+        .browsing_context_get_tree(params)
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.getTree", e))?;
+    Ok(result
+        .contexts
+        .into_iter()
+        .next()
+        .map(|info| info.url)
+        .unwrap_or_default())
+}
+
+/// Creates a directory named after the current time and the action under `parent`,
+/// adding a suffix if a failure at the same millisecond already created it.
+fn create_dir(parent: &Path, action: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(parent)?;
+    let name = format!("{}-{}", timestamp(), action);
+    for attempt in 1.. {
+        let dir = match attempt {
+            1 => parent.join(&name),
+            _ => parent.join(format!("{}-{}", name, attempt)),
+        };
+        match fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// Returns the current UTC time as `YYYYMMDD-HHMMSS-mmm`, which sorts chronologically.
+fn timestamp() -> String {
+    format_timestamp(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+/// Formats a time since the Unix epoch as `YYYYMMDD-HHMMSS-mmm`.
+fn format_timestamp(since_epoch: Duration) -> String {
+    let (days, seconds) = (since_epoch.as_secs() / 86_400, since_epoch.as_secs() % 86_400);
+    let (year, month, day) = civil_date(days as i64);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Returns the year, month and day of the civil date `days` days after 1970-01-01.
+fn civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(-1), (1969, 12, 31));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(20_744), (2026, 10, 18));
+        assert_eq!(civil_date(47_541), (2100, 3, 1));
+    }
+
+    #[test]
+    fn timestamps_are_zero_padded() {
+        assert_eq!(format_timestamp(Duration::ZERO), "19700101-000000-000");
+        assert_eq!(format_timestamp(Duration::from_millis(1_792_326_896_789)), "20261018-123456-789");
+    }
+}
@@ -5,23 +5,24 @@ use webdriverbidi::model::script::{
 };
 
 use crate::error::BrowserError;
+use crate::{frame, script, selector};
 
 /// Assert that an element is present in the current page.
 pub async fn assert_element_present(
     session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     selector: &str,
 ) -> Result<bool, BrowserError> {
-    // Use double quotes for the JavaScript string to avoid conflicts with CSS selectors
-    let escaped_selector = selector.replace("\"", "\\\"");
-    let script = format!("document.querySelector(\"{}\") !== null", escaped_selector);
-    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), None));
+    let (context, selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
+    let script = format!("{} !== null", selector::query_selector(&selector));
+    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), sandbox.map(str::to_string)));
     let params = EvaluateParameters::new(script, target, false, None, None, None);
     
     let result = session
         .script_evaluate(params)
         .await
-        .map_err(|e| BrowserError::Assertion(format!("Script evaluation failed: {}", e)))?;
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
   
     match result {
         EvaluateResult::EvaluateResultSuccess(success) => {
@@ -36,7 +37,7 @@ pub async fn assert_element_present(
             }
         }
         EvaluateResult::EvaluateResultException(exception) => {
-            Err(BrowserError::Assertion(format!("Script exception: {:?}", exception.exception_details)))
+            Err(script::exception_details_error(&exception.exception_details))
         }
         EvaluateResult::EmptyResult(_) => {
             Err(BrowserError::Assertion("Empty result from script evaluation".to_string()))
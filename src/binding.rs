@@ -0,0 +1,206 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use log::{debug, error};
+use serde_json::{Value, json};
+use tokio::task::JoinHandle;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::channel::{self, ChannelMessage, ChannelRegistry};
+use crate::command;
+use crate::error::BrowserError;
+use crate::events::EventDispatcher;
+use crate::preload::{self, PreloadScript};
+
+// --------------------------------------------------
+
+/// Defines `globalThis[name]`, which posts its arguments through the channel
+/// function and returns a promise settled by `settle_call_fn`.
+fn binding_fn(name: &str) -> String {
+    format!(
+        r#"(send) => {{
+    const name = {};
+    const bindings = (globalThis.__autom8Bindings ??= {{}});
+    if (bindings[name]) return;
+    const pending = new Map();
+    let nextId = 0;
+    bindings[name] = {{
+        settle(id, ok, value) {{
+            const call = pending.get(id);
+            if (!call) return;
+            pending.delete(id);
+            ok ? call.resolve(value) : call.reject(new Error(value));
+        }},
+    }};
+    globalThis[name] = (...args) => new Promise((resolve, reject) => {{
+        const id = nextId++;
+        pending.set(id, {{ resolve, reject }});
+        send(JSON.stringify({{ id, args }}));
+    }});
+}}"#,
+        Value::from(name)
+    )
+}
+
+/// Settles the pending call `id` of the binding `name`.
+fn settle_call_fn(name: &str, id: u64, result: Result<Value, String>) -> String {
+    let (ok, value) = match result {
+        Ok(value) => (true, value),
+        Err(message) => (false, Value::from(message)),
+    };
+    format!(
+        "() => globalThis.__autom8Bindings?.[{}]?.settle({}, {}, {})",
+        Value::from(name),
+        id,
+        ok,
+        value
+    )
+}
+
+/// Removes the binding `name` from a document.
+fn remove_binding_fn(name: &str) -> String {
+    format!(
+        "() => {{ const name = {}; delete globalThis[name]; delete globalThis.__autom8Bindings?.[name]; }}",
+        Value::from(name)
+    )
+}
+
+// --------------------------------------------------
+
+/// A Rust function exposed to page JavaScript.
+///
+/// The preload script is removed, the call loop stopped and the channel closed when dropped.
+pub struct Binding {
+    _script: PreloadScript,
+    task: JoinHandle<()>,
+    channel: String,
+    channels: Arc<ChannelRegistry>,
+}
+
+impl Drop for Binding {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.channels.close(&self.channel);
+    }
+}
+
+// --------------------------------------------------
+
+/// Exposes `handler` as `globalThis[name]` in every new document and, if given,
+/// in the current document of `context`.
+pub async fn expose_function<F, Fut>(
+    session: &mut WebDriverBiDiSession,
+    events: &EventDispatcher,
+    channels: &Arc<ChannelRegistry>,
+    context: Option<&str>,
+    name: &str,
+    handler: F,
+) -> Result<Binding, BrowserError>
+where
+    F: Fn(Value) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Value> + Send + 'static,
+{
+    let (channel, mut receiver) = channel::open_channel(session, events, channels).await?;
+    let script = match install(session, &channel, context, name).await {
+        Ok(script) => script,
+        Err(e) => {
+            channels.close(&channel);
+            return Err(e);
+        }
+    };
+
+    let handler = Arc::new(handler);
+    let call_session = session.clone();
+    let binding_name = name.to_string();
+    let task = tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            let Some((id, args)) = parse_call(&message) else {
+                error!("Unexpected call of {}: {:?}", binding_name, message.data);
+                continue;
+            };
+            debug!("{} called with {}", binding_name, args);
+            let handler = handler.clone();
+            let mut session = call_session.clone();
+            let name = binding_name.clone();
+            tokio::spawn(async move {
+                let result = tokio::spawn(handler(args))
+                    .await
+                    .map_err(|e| format!("{} failed in Rust: {}", name, e));
+                let params = json!({
+                    "functionDeclaration": settle_call_fn(&name, id, result),
+                    "target": { "realm": message.realm },
+                    "awaitPromise": false,
+                });
+                if let Err(e) = command::send(&mut session, "script.callFunction", params).await {
+                    error!("Returning the result of {} failed: {}", name, e);
+                }
+            });
+        }
+    });
+
+    debug!("Function exposed: {}", name);
+    Ok(Binding {
+        _script: script,
+        task,
+        channel,
+        channels: channels.clone(),
+    })
+}
+
+/// Defines the binding `name` posting to `channel` in every new document and, if given,
+/// in the current document of `context`.
+async fn install(
+    session: &mut WebDriverBiDiSession,
+    channel: &str,
+    context: Option<&str>,
+    name: &str,
+) -> Result<PreloadScript, BrowserError> {
+    let function_declaration = binding_fn(name);
+    let id = preload::add_preload_script(
+        session,
+        &function_declaration,
+        Some(vec![channel::channel_argument(channel)]),
+        None,
+        None,
+    )
+    .await?;
+    let script = PreloadScript::new(session.clone(), id);
+
+    if let Some(context) = context {
+        let params = json!({
+            "functionDeclaration": function_declaration,
+            "arguments": [{ "type": "channel", "value": { "channel": channel } }],
+            "target": { "context": context },
+            "awaitPromise": false,
+        });
+        command::send(session, "script.callFunction", params)
+            .await
+            .map_err(|e| BrowserError::command("script.callFunction", e))?;
+    }
+    Ok(script)
+}
+
+/// Removes the binding `name` from the current document of `context`.
+pub async fn remove_from_context(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    name: &str,
+) -> Result<(), BrowserError> {
+    let params = json!({
+        "functionDeclaration": remove_binding_fn(name),
+        "target": { "context": context },
+        "awaitPromise": false,
+    });
+    command::send(session, "script.callFunction", params)
+        .await
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
+    Ok(())
+}
+
+/// Parses the `{ id, args }` payload posted by the binding.
+fn parse_call(message: &ChannelMessage) -> Option<(u64, Value)> {
+    let call: Value = serde_json::from_str(message.data.as_str()?).ok()?;
+    Some((call.get("id")?.as_u64()?, call.get("args")?.clone()))
+}
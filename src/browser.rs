@@ -1,14 +1,45 @@
-use log::debug;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use log::{debug, error};
+use serde_json::Value;
 
 // --------------------------------------------------
 
 use webdriverbidi::model::browsing_context::GetTreeParameters;
+use webdriverbidi::model::session::SubscriptionRequest;
 use webdriverbidi::session::WebDriverBiDiSession;
 
 // --------------------------------------------------
 
+use crate::artifacts::{self, ArtifactPolicy, ConsoleLog, Failure};
+use crate::binding::{self, Binding};
+use crate::channel::{self, ChannelReceiver, ChannelRegistry};
+use crate::connect::{self, Connection, ReconnectPolicy, Watchdog};
+use crate::device::Device;
+use crate::dialog::{self, Dialog, DialogAction, DialogPolicy, DialogRegistry};
+use crate::element::Element;
+use crate::emulation::{self, DEFAULT_USER_CONTEXT, Emulation, EmulationScope, ShimRegistry};
 use crate::error::BrowserError;
-use crate::{assertions, extract, input, local_storage, nav, screenshot};
+use crate::events::{EventDispatcher, EventReceiver};
+use crate::frame::Frame;
+use crate::handle::{self, JsHandle};
+use crate::launcher::DriverProcess;
+use crate::options::BrowserOptions;
+use crate::page::Page;
+use crate::preload::{self, PreloadScript};
+use crate::print::PrintOptions;
+use crate::recording::{Recorder, RecordingOptions};
+use crate::scope::ContextScope;
+use crate::realm::{self, RealmEventReceiver, RealmEventRegistry, RealmFilter, RealmInfo};
+use crate::script::{self, DEFAULT_SANDBOX};
+use crate::screenshot::ScreenshotOptions;
+use crate::visual::ScreenshotMatchOptions;
+use crate::user_context;
 
 // --------------------------------------------------
 
@@ -18,19 +49,93 @@ pub type CapabilityRequest = webdriverbidi::webdriver::capabilities::CapabilityR
 
 // --------------------------------------------------
 
+/// How long a browser dropped without being closed waits for its session to end.
+const DROP_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long `close_with_timeout` lets the browser close over BiDi before killing it.
+const FORCED_CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// --------------------------------------------------
+
 /// The `Browser` struct provides an interface for managing a WebDriver BiDi session
 /// and performing browser operations such as opening, closing, and navigating to URLs.
 ///
-/// # Fields
-/// - `webdriverbidi_session`: An instance of `WebDriverBiDiSession` which manages the WebDriver BiDi session.
-/// - `browsing_context`: An optional `String` that holds the current browsing context identifier.
+/// # Concurrency
+/// A `Browser` is a cheap handle to a shared session: clones drive the same browser and
+/// can be moved to other tasks. Commands sent from several tasks are pipelined over the
+/// WebSocket connection instead of waiting for each other. Events, e.g. channel messages,
+/// dialogs or realm events, are read by a task owned by the browser and dispatched from
+/// another one, so they are delivered while commands are in flight and a slow receiver
+/// or dialog callback never holds up the others.
+///
+/// ```rust,no_run
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let (title, heading) = tokio::join!(
+///     browser.evaluate("document.title"),
+///     browser.extract_inner_text("h1"),
+/// );
+/// println!("{} / {}", title?, heading?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Sandboxing
+/// Element lookups, extraction, assertions, input, local storage access and page load checks
+/// run in the `"autom8"` sandbox realm, isolated from page scripts that override globals such
+/// as `document.querySelector` or `Array.prototype`. Use `set_sandbox` to pick another sandbox
+/// or to run them in the page realm.
+///
+/// # Selectors
+/// Element selectors are CSS selectors, extended with:
+/// - Shadow piercing: `host >>> inner` matches `inner` inside the shadow root of `host`,
+///   descending through nested open shadow roots. A leading `>>> inner` searches the whole
+///   document, including every open shadow root.
+/// - Frame piercing: `iframe#checkout |> button.pay` matches `button.pay` inside the frame
+///   whose name, id or selector is `iframe#checkout`.
 ///
 /// # Errors
 /// Methods in this struct return `Result` types and may produce errors related to session creation,
 /// navigation, and other browser operations. These errors are encapsulated in the `BrowserError` enum.
+/// Failed WebDriver BiDi commands are reported as `Protocol`, `Transport` or `Timeout` errors,
+/// whatever the method.
+#[derive(Clone)]
 pub struct Browser {
-    pub webdriverbidi_session: WebDriverBiDiSession,
-    pub browsing_context: Option<String>,
+    inner: Arc<Shared>,
+}
+
+/// The state shared by the clones of a `Browser`.
+///
+/// The locks are never held across an await: commands run on clones of the session,
+/// which share its WebSocket connection and pending command table.
+struct Shared {
+    session: Mutex<WebDriverBiDiSession>,
+    connection: Mutex<Option<Connection>>,
+    events: Arc<EventDispatcher>,
+    browsing_context: Mutex<Option<String>>,
+    dialogs: Arc<DialogRegistry>,
+    emulation_shims: ShimRegistry,
+    channels: Arc<ChannelRegistry>,
+    // `None` while the function is being exposed, so that no other clone exposes it too
+    bindings: Mutex<HashMap<String, Option<Binding>>>,
+    sandbox: Mutex<Option<String>>,
+    realm_events: Arc<RealmEventRegistry>,
+    recorder: Mutex<Option<Recorder>>,
+    process: Mutex<Option<DriverProcess>>,
+    attached: bool,
+    closed: AtomicBool,
+    connection_lost: Arc<AtomicBool>,
+    watchdog: Mutex<Option<Watchdog>>,
+    reconnect_policy: Mutex<Option<ReconnectPolicy>>,
+    artifact_policy: Mutex<Option<ArtifactPolicy>>,
+    console: Arc<ConsoleLog>,
+    // Serializes closing and reconnecting between clones
+    lifecycle: tokio::sync::Mutex<()>,
+}
+
+/// Locks a mutex, ignoring poisoning: the shared state stays consistent between
+/// statements, so a panic in another task doesn't invalidate it.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 // --------------------------------------------------
@@ -40,13 +145,79 @@ impl Browser {
     ///
     /// # Errors
     ///
-    /// A `BrowserError::NavigationError` error is returned if the context value is None.
+    /// A `BrowserError::Disconnected` error is returned if the browser is closed or its
+    /// connection lost, and a `BrowserError::Navigation` error if the context value is None.
     fn get_context(&self) -> Result<String, BrowserError> {
-        let ctx = self
-            .browsing_context
-            .as_ref()
-            .ok_or_else(|| BrowserError::Navigation("No browsing context available".to_owned()))?;
-        Ok(ctx.to_string())
+        self.check_usable()?;
+        self.browsing_context()
+            .ok_or_else(|| BrowserError::Navigation("No browsing context available".to_owned()))
+    }
+
+    /// Returns the browsing context like `get_context`, first reconnecting with the
+    /// reconnect policy, if any, when the connection to the driver was lost.
+    pub(crate) async fn active_context(&self) -> Result<String, BrowserError> {
+        self.ensure_connected().await?;
+        self.get_context()
+    }
+
+    /// Reconnects with the reconnect policy, if any, when the connection to the driver
+    /// was lost.
+    ///
+    /// # Errors
+    /// A `BrowserError::Disconnected` error is returned if the browser is closed or its
+    /// connection still lost.
+    pub(crate) async fn ensure_connected(&self) -> Result<(), BrowserError> {
+        if self.inner.connection_lost.load(Ordering::SeqCst) {
+            let policy = lock(&self.inner.reconnect_policy).clone();
+            if let Some(policy) = policy {
+                let _lifecycle = self.inner.lifecycle.lock().await;
+                // Another clone may have reconnected while we were waiting
+                let lost = self.inner.connection_lost.load(Ordering::SeqCst);
+                if lost && !self.inner.closed.load(Ordering::SeqCst) {
+                    self.reconnect_with(&policy).await?;
+                }
+            }
+        }
+        self.check_usable()
+    }
+
+    /// Returns a `BrowserError::Disconnected` error if the browser is closed or its
+    /// connection lost.
+    fn check_usable(&self) -> Result<(), BrowserError> {
+        if self.inner.closed.load(Ordering::SeqCst) {
+            return Err(BrowserError::Disconnected("The browser is closed".to_owned()));
+        }
+        if self.inner.connection_lost.load(Ordering::SeqCst) {
+            return Err(BrowserError::Disconnected(
+                "The connection to the driver was lost, call reconnect() or set a reconnect policy"
+                    .to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the scope of the actions run against the current browsing context.
+    fn scope(&self) -> ContextScope<'_> {
+        ContextScope::new(self, None, self.sandbox())
+    }
+
+    /// Returns a handle to the WebDriver BiDi session, sharing its connection with the browser.
+    ///
+    /// The connection is read by autom8, so handlers registered on the session with
+    /// `register_event_handler` are never called: use `subscribe_events` instead.
+    pub fn webdriverbidi_session(&self) -> WebDriverBiDiSession {
+        lock(&self.inner.session).clone()
+    }
+
+    /// Returns the current browsing context identifier, if any.
+    pub fn browsing_context(&self) -> Option<String> {
+        lock(&self.inner.browsing_context).clone()
+    }
+
+    /// Makes a top-level browsing context, e.g. one returned by `contexts`, the current one
+    /// for every clone of the browser.
+    pub fn set_browsing_context(&self, context: &str) {
+        *lock(&self.inner.browsing_context) = Some(context.to_string());
     }
 }
 
@@ -68,10 +239,7 @@ impl Browser {
             host, port
         );
         let capabilities = CapabilitiesRequest::default();
-        Self {
-            webdriverbidi_session: WebDriverBiDiSession::new(host.to_string(), port, capabilities),
-            browsing_context: None,
-        }
+        Self::from_session(WebDriverBiDiSession::new(host.to_string(), port, capabilities), false)
     }
 
     /// Creates a new `Browser` instance with the specified capabilities, host, and port.
@@ -88,58 +256,354 @@ impl Browser {
             "Creating a new Browser instance with host: {}, port: {}, capabilities: {:?}",
             host, port, capabilities
         );
+        Self::from_session(WebDriverBiDiSession::new(host.to_string(), port, capabilities), false)
+    }
+
+    /// Creates a new `Browser` instance with typed options, for a driver already running
+    /// on the specified host and port. Use `BrowserOptions::launch` to start a local driver.
+    ///
+    /// # Arguments
+    /// - `options`: The options compiled into the capabilities of the session.
+    /// - `host`: The host address of the WebDriver BiDi server.
+    /// - `port`: The port number of the WebDriver BiDi server.
+    ///
+    /// # Returns
+    /// A new instance of `Browser`.
+    pub fn new_with_options(options: &BrowserOptions, host: &str, port: u16) -> Self {
+        Self::new_with_capabilities(options.to_capabilities(), host, port)
+    }
+
+    /// Creates a `Browser` instance around a session, `attached` if it was created by
+    /// another client.
+    fn from_session(session: WebDriverBiDiSession, attached: bool) -> Self {
         Self {
-            webdriverbidi_session: WebDriverBiDiSession::new(host.to_string(), port, capabilities),
-            browsing_context: None,
+            inner: Arc::new(Shared {
+                session: Mutex::new(session),
+                connection: Mutex::new(None),
+                events: Arc::default(),
+                browsing_context: Mutex::new(None),
+                dialogs: Arc::default(),
+                emulation_shims: ShimRegistry::default(),
+                channels: Arc::default(),
+                bindings: Mutex::new(HashMap::new()),
+                sandbox: Mutex::new(Some(DEFAULT_SANDBOX.to_string())),
+                realm_events: Arc::default(),
+                recorder: Mutex::new(None),
+                process: Mutex::new(None),
+                attached,
+                closed: AtomicBool::new(false),
+                connection_lost: Arc::default(),
+                watchdog: Mutex::new(None),
+                reconnect_policy: Mutex::new(None),
+                artifact_policy: Mutex::new(None),
+                console: Arc::default(),
+                lifecycle: tokio::sync::Mutex::new(()),
+            }),
         }
     }
 
+    /// Attaches a driver process launched for this browser, killed when the browser
+    /// is closed or its last clone dropped.
+    pub(crate) fn attach_process(&self, process: DriverProcess) {
+        *lock(&self.inner.process) = Some(process);
+    }
+
     /// Starts a new WebDriver BiDi session and retrieves the browsing context.
     ///
     /// # Errors
-    /// Returns a `BrowserError::SessionCreationError` if the session could not be started
-    /// or if the `browsingContext.getTree` command fails.
-    pub async fn open(&mut self) -> Result<(), BrowserError> {
+    /// Returns a `BrowserError::SessionCreation` if the session could not be started, or a
+    /// `BrowserError::Protocol` if the `browsingContext.getTree` command fails.
+    pub async fn open(&self) -> Result<(), BrowserError> {
         debug!("Starting the WebDriver BiDi session");
-        self.webdriverbidi_session.start().await.map_err(|e| {
-            BrowserError::SessionCreation(format!(
-                "Starting the WebDriverBiDi session failed: {}",
-                e
-            ))
-        })?;
+        let mut session = self.webdriverbidi_session();
+        let connection =
+            connect::start(&mut session, self.inner.events.clone(), self.inner.connection_lost.clone()).await?;
+        self.set_connection(session, connection);
         debug!("WebDriver BiDi session started successfully");
+        self.watch();
+        self.load_browsing_context().await
+    }
+
+    /// Connects to a session that already exists, e.g. one created by another client or
+    /// exposed by a grid, through its BiDi WebSocket URL. No new session is created and the
+    /// first existing top-level browsing context becomes the current one.
+    ///
+    /// # Arguments
+    /// - `ws_url`: The WebSocket URL of the session, e.g. `ws://localhost:4444/session/<id>`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::SessionCreation` if the URL doesn't point to a session,
+    /// the connection fails or the browsing contexts can't be retrieved.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let browser = autom8::Browser::connect("ws://localhost:4444/session/f3b2c1").await?;
+    /// browser.load("https://example.com").await?;
+    /// browser.disconnect().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect(ws_url: &str) -> Result<Self, BrowserError> {
+        let session = WebDriverBiDiSession::new(String::new(), 0, CapabilitiesRequest::default());
+        let browser = Self::from_session(session, true);
+        let mut session = browser.webdriverbidi_session();
+        let connection = connect::attach(
+            &mut session,
+            ws_url,
+            browser.inner.events.clone(),
+            browser.inner.connection_lost.clone(),
+        )
+        .await?;
+        browser.set_connection(session, connection);
+        browser.watch();
+        browser.load_browsing_context().await?;
+        debug!("Connected to {}", ws_url);
+        Ok(browser)
+    }
+
+    /// Stores the session once connected, with the connection reading its WebSocket.
+    fn set_connection(&self, session: WebDriverBiDiSession, connection: Connection) {
+        *lock(&self.inner.session) = session;
+        *lock(&self.inner.connection) = Some(connection);
+    }
+
+    /// Retrieves the top-level browsing contexts and makes the first one current.
+    async fn load_browsing_context(&self) -> Result<(), BrowserError> {
+        let contexts = self.contexts().await?;
+        let context = contexts.into_iter().next().ok_or_else(|| {
+            BrowserError::SessionCreation("The browser has no browsing context".to_string())
+        })?;
+        debug!("Browsing context retrieved: {}", context);
+        self.set_browsing_context(&context);
+        Ok(())
+    }
 
+    /// Returns the ids of the top-level browsing contexts (tabs and windows) of the session.
+    /// Pass one to `set_browsing_context` to switch to it.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.getTree` command fails.
+    pub async fn contexts(&self) -> Result<Vec<String>, BrowserError> {
         debug!("Retrieving the browsing context tree");
-        let get_tree_params = GetTreeParameters::new(None, None);
+        let get_tree_params = GetTreeParameters::new(Some(0), None);
         let get_tree_rslt = self
-            .webdriverbidi_session
+            .webdriverbidi_session()
             .browsing_context_get_tree(get_tree_params)
             .await
-            .map_err(|e| {
-                BrowserError::SessionCreation(format!(
-                    "The browsingContext.getTree command failed: {}",
-                    e
-                ))
-            })?;
-        self.browsing_context = Some(get_tree_rslt.contexts[0].context.clone());
-        debug!("Browsing context retrieved: {:?}", self.browsing_context);
+            .map_err(|e| BrowserError::command("browsingContext.getTree", e))?;
+        Ok(get_tree_rslt
+            .contexts
+            .into_iter()
+            .map(|info| info.context)
+            .collect())
+    }
+
+    /// Closes the connection to the session, leaving the session and the browser running.
+    /// Other clients can keep using the session, or reconnect to it with `Browser::connect`.
+    /// A recording in progress is stopped and saved, as with `stop_recording`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::SessionClosing` if the WebSocket connection couldn't be closed.
+    pub async fn disconnect(&self) -> Result<(), BrowserError> {
+        debug!("Disconnecting from the WebDriver BiDi session");
+        self.stop_background_tasks();
+        connect::disconnect(&mut self.webdriverbidi_session()).await
+    }
+
+    /// Starts checking the connection to the driver in the background.
+    fn watch(&self) {
+        self.inner.connection_lost.store(false, Ordering::SeqCst);
+        let watchdog = Watchdog::start(&self.webdriverbidi_session(), self.inner.connection_lost.clone());
+        *lock(&self.inner.watchdog) = Some(watchdog);
+    }
+
+    /// Stops and saves the recording, stops the exposed functions and the health checks,
+    /// and flags the connection as lost.
+    fn stop_background_tasks(&self) {
+        lock(&self.inner.watchdog).take();
+        let recorder = lock(&self.inner.recorder).take();
+        if let Some(recorder) = recorder {
+            match recorder.stop() {
+                Ok(paths) => debug!("Recording saved: {:?}", paths),
+                Err(e) => error!("Saving the recording failed: {}", e),
+            }
+        }
+        lock(&self.inner.bindings).clear();
+        self.inner.connection_lost.store(true, Ordering::SeqCst);
+    }
+
+    /// Checks that the driver still answers, using the `session.status` command.
+    ///
+    /// The connection is also checked in the background every few seconds, and considered
+    /// lost after several checks in a row went unanswered. Once it is lost, methods return a `BrowserError::Disconnected`, or reconnect first when a
+    /// reconnect policy is set.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the driver doesn't answer.
+    pub async fn ping(&self) -> Result<(), BrowserError> {
+        let result = connect::ping(&mut self.webdriverbidi_session()).await;
+        if result.is_err() {
+            lock(&self.inner.watchdog).take();
+            self.inner.connection_lost.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Returns `false` once the connection to the driver is known to be lost or closed.
+    pub fn is_connected(&self) -> bool {
+        !self.inner.connection_lost.load(Ordering::SeqCst)
+    }
+
+    /// Sets how the browser reconnects, before the next command, after losing its
+    /// connection to the driver. `None`, the default, disables automatic reconnection.
+    pub fn set_reconnect_policy(&self, policy: Option<ReconnectPolicy>) {
+        *lock(&self.inner.reconnect_policy) = policy;
+    }
+
+    /// Reopens the WebSocket connection to the session, using the reconnect policy or
+    /// the default one. Event handlers, frames, elements and handles keep working.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if every attempt failed.
+    pub async fn reconnect(&self) -> Result<(), BrowserError> {
+        let policy = lock(&self.inner.reconnect_policy).clone().unwrap_or_default();
+        let _lifecycle = self.inner.lifecycle.lock().await;
+        self.reconnect_with(&policy).await
+    }
+
+    async fn reconnect_with(&self, policy: &ReconnectPolicy) -> Result<(), BrowserError> {
+        lock(&self.inner.watchdog).take();
+        let connection = lock(&self.inner.connection)
+            .clone()
+            .ok_or_else(|| BrowserError::Disconnected("The session was never connected".to_string()))?;
+        connect::reconnect(&mut self.webdriverbidi_session(), &connection, policy).await?;
+        self.watch();
         Ok(())
     }
 
-    /// Closes the WebDriver BiDi session.
+    /// Closes the WebDriver BiDi session, then kills the driver if it was launched by autom8.
+    ///
+    /// Closing is idempotent: once closed, further calls, from any clone, return `Ok(())`.
+    /// The browser is considered closed even if ending the session failed. A recording
+    /// in progress is stopped and saved, as with `stop_recording`.
+    ///
+    /// A browser whose last clone is dropped without being closed, e.g. when a test panics,
+    /// ends its session and kills its driver on drop. Browsers attached with
+    /// `Browser::connect` are left running.
     ///
     /// # Errors
-    /// Returns a `BrowserError::SessionClosingError` if the session could not be closed.
-    pub async fn close(&mut self) -> Result<(), BrowserError> {
+    /// Returns a `BrowserError::SessionClosing` if the session could not be closed.
+    pub async fn close(&self) -> Result<(), BrowserError> {
+        let _lifecycle = self.inner.lifecycle.lock().await;
+        if self.inner.closed.load(Ordering::SeqCst) {
+            debug!("The browser is already closed");
+            return Ok(());
+        }
         debug!("Closing the WebDriver BiDi session");
-        self.webdriverbidi_session.close().await.map_err(|e| {
+        lock(&self.inner.watchdog).take();
+        let result = self.end_session().await;
+        if result.is_ok() {
+            debug!("WebDriver BiDi session closed successfully");
+        }
+        self.mark_closed();
+        result
+    }
+
+    /// Closes the browser like `close`, but gives up on the driver after `timeout`.
+    ///
+    /// When the driver doesn't respond in time and was launched by autom8, the browser is
+    /// asked to close over BiDi, then the driver and the browser are killed. The connection
+    /// to any other driver is abandoned.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::SessionClosing` if the session could not be closed, or a
+    /// `BrowserError::Timeout` if the driver didn't respond in time and wasn't launched by autom8.
+    pub async fn close_with_timeout(&self, timeout: Duration) -> Result<(), BrowserError> {
+        match tokio::time::timeout(timeout, self.close()).await {
+            Ok(result) => result,
+            Err(_) => {
+                let launched = lock(&self.inner.process).is_some();
+                if launched {
+                    let mut session = self.webdriverbidi_session();
+                    let close = connect::close_browser(&mut session);
+                    match tokio::time::timeout(FORCED_CLOSE_TIMEOUT, close).await {
+                        Ok(Ok(())) => debug!("The browser closed"),
+                        Ok(Err(e)) => debug!("Closing the browser failed: {}", e),
+                        Err(_) => debug!("The browser didn't close in time"),
+                    }
+                }
+                self.mark_closed();
+                if launched {
+                    debug!("The driver didn't respond in time and was killed with the browser");
+                    return Ok(());
+                }
+                Err(BrowserError::timeout("Closing the session", timeout))
+            }
+        }
+    }
+
+    /// Ends the session: over HTTP for sessions created by autom8, by closing the browser
+    /// over BiDi for attached ones.
+    async fn end_session(&self) -> Result<(), BrowserError> {
+        let mut session = self.webdriverbidi_session();
+        if self.inner.attached {
+            return connect::close_browser(&mut session).await;
+        }
+        session.close().await.map_err(|e| {
             BrowserError::SessionClosing(format!(
                 "Closing the WebDriver BiDi session failed: {}",
                 e
             ))
-        })?;
-        debug!("WebDriver BiDi session closed successfully");
-        Ok(())
+        })
+    }
+
+    /// Stops the background tasks and the launched driver, and rejects further commands.
+    fn mark_closed(&self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.stop_background_tasks();
+        lock(&self.inner.connection).take();
+        let process = lock(&self.inner.process).take();
+        if let Some(mut process) = process {
+            process.shutdown();
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.watchdog.get_mut().unwrap_or_else(PoisonError::into_inner).take();
+        let session = self.session.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if *self.closed.get_mut() || self.attached || session.session_id.is_empty() {
+            return;
+        }
+
+        // The session is ended before the driver is killed. Within a runtime, this happens
+        // on a task of its own, so that dropping doesn't block the caller; if the runtime
+        // shuts down first, e.g. after a test panicked, the driver is killed right away.
+        let mut session = session.clone();
+        let process = self.process.get_mut().unwrap_or_else(PoisonError::into_inner).take();
+        debug!("Browser dropped without being closed, ending session {}", session.session_id);
+        let cleanup = async move {
+            match tokio::time::timeout(DROP_CLOSE_TIMEOUT, session.close()).await {
+                Ok(Ok(())) => debug!("Session {} ended", session.session_id),
+                Ok(Err(e)) => error!("Ending the dropped session failed: {}", e),
+                Err(_) => error!("Ending the dropped session timed out"),
+            }
+            drop(process);
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(cleanup);
+            }
+            Err(_) => match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime.block_on(cleanup),
+                Err(e) => error!("Starting a runtime to end the dropped session failed: {}", e),
+            },
+        }
     }
 }
 
@@ -153,47 +617,37 @@ impl Browser {
     /// - `url`: The URL to navigate to.
     ///
     /// # Errors
-    /// Returns a `BrowserError::NavigationError` if no browsing context is available
-    /// or if the navigation command fails.
-    pub async fn load(&mut self, url: &str) -> Result<(), BrowserError> {
-        debug!("Navigating to URL: {}", url);
-        let ctx = self.get_context()?;
-        nav::load(&mut self.webdriverbidi_session, ctx, url).await?;
-        debug!("Navigation to URL: {} completed successfully", url);
-        Ok(())
+    /// Returns a `BrowserError::Navigation` if no browsing context is available,
+    /// or a `BrowserError::Protocol` if the `browsingContext.navigate` command fails.
+    pub async fn load(&self, url: &str) -> Result<(), BrowserError> {
+        self.scope().load(url).await
     }
 
     /// Navigates to the previous page in history.
     ///
     /// # Errors
-    /// Returns a `BrowserError::NavigationError` if no browsing context is available
-    /// or if navigating back failed.
-    pub async fn go_back(&mut self) -> Result<(), BrowserError> {
-        let ctx = self.get_context()?;
-        nav::go_back(&mut self.webdriverbidi_session, ctx).await?;
-        Ok(())
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` if the `browsingContext.traverseHistory` command fails.
+    pub async fn go_back(&self) -> Result<(), BrowserError> {
+        self.scope().go_back().await
     }
 
     /// Navigates to the next page in history.
     ///
     /// # Errors
-    /// Returns a `BrowserError::NavigationError` if no browsing context is available
-    /// or if navigating forward failed.
-    pub async fn go_forward(&mut self) -> Result<(), BrowserError> {
-        let ctx = self.get_context()?;
-        nav::go_forward(&mut self.webdriverbidi_session, ctx).await?;
-        Ok(())
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` if the `browsingContext.traverseHistory` command fails.
+    pub async fn go_forward(&self) -> Result<(), BrowserError> {
+        self.scope().go_forward().await
     }
 
     /// Reloads the current page.
     ///
     /// # Errors
-    /// Returns a `BrowserError::NavigationError` if no browsing context is available
-    /// or if navigating forward failed.
-    pub async fn reload(&mut self) -> Result<(), BrowserError> {
-        let ctx = self.get_context()?;
-        nav::reload(&mut self.webdriverbidi_session, ctx).await?;
-        Ok(())
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` if the `browsingContext.reload` command fails.
+    pub async fn reload(&self) -> Result<(), BrowserError> {
+        self.scope().reload().await
     }
 
     /// Waits for the current page to finish loading.
@@ -202,10 +656,515 @@ impl Browser {
     /// - `timeout_ms`: Maximum time to wait for page load in milliseconds (default: 10000)
     ///
     /// # Errors
-    /// Returns a `BrowserError::NavigationError` if the page doesn't load within the timeout.
-    pub async fn wait_for_page_load(&mut self, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+    /// Returns a `BrowserError::Timeout` if the page doesn't load within the timeout.
+    pub async fn wait_for_page_load(&self, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().wait_for_page_load(timeout_ms).await
+    }
+}
+
+// --------------------------------------------------
+
+// User contexts and pages
+impl Browser {
+    /// Creates a user context, an isolated profile like a private window: its pages share
+    /// no cookies, storage or cache with the pages of other user contexts.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browser.createUserContext` command fails.
+    pub async fn new_user_context(&self) -> Result<String, BrowserError> {
+        user_context::create_user_context(&mut self.webdriverbidi_session()).await
+    }
+
+    /// Returns the ids of the user contexts of the browser, starting with `"default"`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browser.getUserContexts` command fails.
+    pub async fn user_contexts(&self) -> Result<Vec<String>, BrowserError> {
+        user_context::get_user_contexts(&mut self.webdriverbidi_session()).await
+    }
+
+    /// Removes a user context and closes all of its pages. The default user context
+    /// can't be removed.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::UserContext` if the user context is the default one,
+    /// or a `BrowserError::Protocol` if the `browser.removeUserContext` command fails.
+    pub async fn remove_user_context(&self, user_context: &str) -> Result<(), BrowserError> {
+        user_context::remove_user_context(&mut self.webdriverbidi_session(), user_context).await
+    }
+
+    /// Opens a new page (tab) in a user context, or in the default one if `None`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.create` command fails.
+    pub async fn new_page(&self, user_context: Option<&str>) -> Result<Page, BrowserError> {
+        let ctx = user_context::create_page(&mut self.webdriverbidi_session(), user_context).await?;
+        let user_context = user_context.unwrap_or(DEFAULT_USER_CONTEXT).to_string();
+        Ok(self.page_for(ctx, user_context))
+    }
+
+    /// Returns a handle to the current browsing context as a page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available,
+    /// or a `BrowserError::Protocol` if the `browsingContext.getTree` command fails.
+    pub async fn page(&self) -> Result<Page, BrowserError> {
+        let ctx = self.active_context().await?;
+        let user_context = user_context::user_context_of(&mut self.webdriverbidi_session(), &ctx).await?;
+        Ok(self.page_for(ctx, user_context))
+    }
+
+    fn page_for(&self, context: String, user_context: String) -> Page {
+        Page::new(self.clone(), context, user_context, self.sandbox())
+    }
+}
+
+// --------------------------------------------------
+
+// Frames
+impl Browser {
+    /// Returns all frames nested in the current page, in depth-first order.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available,
+    /// or a `BrowserError::Protocol` if the `browsingContext.getTree` command fails.
+    pub async fn frames(&self) -> Result<Vec<Frame>, BrowserError> {
+        self.scope().frames().await
+    }
+
+    /// Returns a handle to a frame of the current page on which the usual
+    /// element, extraction and navigation operations can be performed.
+    ///
+    /// # Arguments
+    /// - `locator`: The name or id of the frame, or a CSS selector matching the
+    ///   `iframe`/`frame` element. Nested frames can be reached by chaining locators
+    ///   with the frame-piercing separator, e.g. `"#outer |> #inner"`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Frame` if no browsing context is available
+    /// or if no matching frame is found.
+    pub async fn frame(&self, locator: &str) -> Result<Frame, BrowserError> {
+        self.scope().frame(locator).await
+    }
+}
+
+// --------------------------------------------------
+
+// Dialogs
+impl Browser {
+    /// Registers a policy closing every JavaScript dialog (`alert`, `confirm`, `prompt`
+    /// and `beforeunload`) opened from now on, replacing any previous policy.
+    ///
+    /// # Arguments
+    /// - `policy`: Whether to accept, dismiss, reply with text or let a callback decide.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `session.subscribe` command fails.
+    pub async fn set_dialog_policy(&self, policy: DialogPolicy) -> Result<(), BrowserError> {
+        dialog::set_dialog_policy(&mut self.webdriverbidi_session(), &self.inner.events, &self.inner.dialogs, policy)
+            .await
+    }
+
+    /// Removes the registered dialog policy. Dialogs opened afterwards stay open
+    /// until they are closed with `handle_dialog`.
+    ///
+    /// The subscription to `browsingContext.userPromptOpened` is kept, so receivers
+    /// returned by `subscribe_events` still get the dialog events.
+    pub fn clear_dialog_policy(&self) {
+        dialog::clear_dialog_policy(&self.inner.events, &self.inner.dialogs);
+    }
+
+    /// Closes the dialog currently open in the current browsing context.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` with the `no such alert` code if no dialog is open.
+    pub async fn handle_dialog(&self, action: DialogAction) -> Result<(), BrowserError> {
+        let ctx = self.active_context().await?;
+        dialog::handle_dialog(&mut self.webdriverbidi_session(), ctx.as_str(), action).await
+    }
+
+    /// Returns the last dialog opened while a dialog policy was registered, if any.
+    ///
+    /// Dialogs are only observed while a policy is registered: those opened before the
+    /// first `set_dialog_policy` call or after `clear_dialog_policy` aren't recorded.
+    pub fn last_dialog(&self) -> Option<Dialog> {
+        self.inner.dialogs.last_dialog()
+    }
+}
+
+// --------------------------------------------------
+
+// Emulation
+impl Browser {
+    /// Returns an emulation handle applying settings to the current browsing context.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available.
+    pub fn emulation(&self) -> Result<Emulation, BrowserError> {
         let ctx = self.get_context()?;
-        nav::wait_for_page_load(&mut self.webdriverbidi_session, ctx, timeout_ms).await?;
+        Ok(self.emulation_for(EmulationScope::Context(ctx)))
+    }
+
+    /// Returns an emulation handle applying settings to the given scope.
+    /// Use `EmulationScope::default_user_context()` to cover every page of the browser.
+    pub fn emulation_for(&self, scope: EmulationScope) -> Emulation {
+        Emulation::new(self.clone(), scope, self.inner.emulation_shims.clone())
+    }
+
+    /// Sets the viewport size of the current page in CSS pixels and, optionally,
+    /// the device pixel ratio.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available,
+    /// or a `BrowserError::Protocol` if the `browsingContext.setViewport` command fails.
+    pub async fn set_viewport(&self, width: u64, height: u64, device_pixel_ratio: Option<f32>) -> Result<(), BrowserError> {
+        let scope = EmulationScope::Context(self.active_context().await?);
+        emulation::set_viewport(&mut self.webdriverbidi_session(), &scope, width, height, device_pixel_ratio).await
+    }
+
+    /// Emulates a device preset, such as `Device::IPHONE_15`, in the current page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` if any of the emulation commands fails.
+    pub async fn emulate_device(&self, device: &Device) -> Result<(), BrowserError> {
+        let scope = EmulationScope::Context(self.active_context().await?);
+        emulation::emulate_device(&mut self.webdriverbidi_session(), &scope, device).await
+    }
+}
+
+// --------------------------------------------------
+
+// JavaScript evaluation
+impl Browser {
+    /// Sets the sandbox realm autom8's element, extraction, assertion, input, local storage
+    /// and page load scripts run in, or `None` to run them in the page realm.
+    /// Defaults to `"autom8"`.
+    pub fn set_sandbox(&self, sandbox: Option<&str>) {
+        *lock(&self.inner.sandbox) = sandbox.map(str::to_string);
+    }
+
+    /// Returns the sandbox realm autom8's own scripts run in, if any.
+    pub fn sandbox(&self) -> Option<String> {
+        lock(&self.inner.sandbox).clone()
+    }
+
+    /// Evaluates a JavaScript expression in the page realm of the current browsing context,
+    /// awaiting it if it's a promise.
+    ///
+    /// # Returns
+    /// The value of the expression as JSON. Values without a JSON representation,
+    /// such as functions or DOM nodes, are returned as `null`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the expression throws, or a
+    /// `BrowserError::Navigation` if no browsing context is available.
+    pub async fn evaluate(&self, expression: &str) -> Result<Value, BrowserError> {
+        self.scope().evaluate(expression, None).await
+    }
+
+    /// Evaluates a JavaScript expression in a named sandbox realm of the current browsing context.
+    /// Sandboxes share the page's DOM but not its globals, and are created on first use.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the expression throws, or a
+    /// `BrowserError::Navigation` if no browsing context is available.
+    pub async fn evaluate_in_sandbox(&self, expression: &str, sandbox: &str) -> Result<Value, BrowserError> {
+        self.scope().evaluate(expression, Some(sandbox)).await
+    }
+
+    /// Calls a JavaScript function in the page realm of the current browsing context,
+    /// awaiting it if it returns a promise.
+    ///
+    /// # Arguments
+    /// - `function_declaration`: A JavaScript function, e.g. `"(a, b) => a + b"`.
+    /// - `arguments`: The arguments passed to the function, as JSON.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the function throws, or a
+    /// `BrowserError::Navigation` if no browsing context is available.
+    pub async fn call_function(&self, function_declaration: &str, arguments: &[Value]) -> Result<Value, BrowserError> {
+        self.scope().call_function(function_declaration, arguments).await
+    }
+
+    /// Evaluates a JavaScript expression in the page realm of the current browsing context
+    /// and returns a handle to the live result, e.g. `"document.body"` or `"window.app.store"`.
+    ///
+    /// The object is kept alive until the handle is disposed or dropped.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the expression throws, or a
+    /// `BrowserError::Navigation` if no browsing context is available.
+    pub async fn evaluate_handle(&self, expression: &str) -> Result<JsHandle, BrowserError> {
+        self.scope().evaluate_handle(expression).await
+    }
+}
+
+// --------------------------------------------------
+
+// Realms
+impl Browser {
+    /// Returns the JavaScript realms matching the filter, including the realms of
+    /// dedicated, shared and service workers.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.getRealms` command fails.
+    pub async fn realms(&self, filter: RealmFilter) -> Result<Vec<RealmInfo>, BrowserError> {
+        realm::get_realms(&mut self.webdriverbidi_session(), &filter).await
+    }
+
+    /// Evaluates a JavaScript expression in a realm, e.g. a worker, awaiting it if it's a promise.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the expression throws.
+    pub async fn evaluate_in_realm(&self, realm: &str, expression: &str) -> Result<Value, BrowserError> {
+        script::evaluate(&mut self.webdriverbidi_session(), script::realm_target(realm), expression).await
+    }
+
+    /// Evaluates a JavaScript expression in a realm, e.g. a worker, and returns a handle
+    /// to the live result.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the expression throws.
+    pub async fn evaluate_handle_in_realm(&self, realm: &str, expression: &str) -> Result<JsHandle, BrowserError> {
+        handle::evaluate_handle(&mut self.webdriverbidi_session(), script::realm_target(realm), expression).await
+    }
+
+    /// Calls a JavaScript function in a realm, e.g. a worker, awaiting it if it returns a promise.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the function throws.
+    pub async fn call_function_in_realm(
+        &self,
+        realm: &str,
+        function_declaration: &str,
+        arguments: &[Value],
+    ) -> Result<Value, BrowserError> {
+        let target = script::realm_target(realm);
+        script::call_function(&mut self.webdriverbidi_session(), target, function_declaration, arguments).await
+    }
+
+    /// Returns a receiver of the realms created and destroyed from now on.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `session.subscribe` command fails.
+    pub async fn realm_events(&self) -> Result<RealmEventReceiver, BrowserError> {
+        realm::realm_events(&mut self.webdriverbidi_session(), &self.inner.events, &self.inner.realm_events).await
+    }
+}
+
+// --------------------------------------------------
+
+// Events
+impl Browser {
+    /// Subscribes to WebDriver BiDi events, e.g. `network.beforeRequestSent`, and returns
+    /// a receiver of the raw event messages. Dropping the receiver stops the delivery.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut requests = browser.subscribe_events(&["network.beforeRequestSent"]).await?;
+    /// browser.load("https://example.com").await?;
+    /// while let Ok(event) = requests.try_recv() {
+    ///     println!("Requested {}", event["params"]["request"]["url"]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `session.subscribe` command fails.
+    pub async fn subscribe_events(&self, events: &[&str]) -> Result<EventReceiver, BrowserError> {
+        let receiver = self.inner.events.receiver(events);
+        let methods = events.iter().map(|event| event.to_string()).collect();
+        self.webdriverbidi_session()
+            .session_subscribe(SubscriptionRequest::new(methods, None, None))
+            .await
+            .map_err(|e| BrowserError::command("session.subscribe", e))?;
+        Ok(receiver)
+    }
+}
+
+// --------------------------------------------------
+
+// Preload scripts
+impl Browser {
+    /// Adds a script that runs before any page script in every new document.
+    ///
+    /// # Arguments
+    /// - `function_declaration`: A JavaScript function, e.g. `"() => { window.__testHook = true; }"`.
+    /// - `sandbox`: The sandbox realm to run the script in, or `None` for the page realm.
+    /// - `contexts`: The top-level browsing contexts to run the script in, or `None` for all.
+    ///
+    /// # Returns
+    /// A handle removing the preload script when dropped.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.addPreloadScript` command fails.
+    pub async fn add_preload_script(
+        &self,
+        function_declaration: &str,
+        sandbox: Option<&str>,
+        contexts: Option<Vec<String>>,
+    ) -> Result<PreloadScript, BrowserError> {
+        let id = preload::add_preload_script(
+            &mut self.webdriverbidi_session(),
+            function_declaration,
+            None,
+            sandbox,
+            contexts,
+        )
+        .await?;
+        Ok(PreloadScript::new(self.webdriverbidi_session(), id))
+    }
+
+    /// Adds a preload script receiving a channel function as its first argument.
+    /// Every value passed to the channel function is delivered to the returned receiver.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+    /// let (_script, mut messages) = browser
+    ///     .add_preload_script_with_channel(
+    ///         "(send) => { window.addEventListener('load', () => send({ title: document.title })); }",
+    ///         None,
+    ///         None,
+    ///     )
+    ///     .await?;
+    /// browser.load("https://example.com").await?;
+    /// if let Some(message) = messages.recv().await {
+    ///     println!("Loaded: {}", message.data["title"]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if subscribing to `script.message`
+    /// or the `script.addPreloadScript` command fails.
+    pub async fn add_preload_script_with_channel(
+        &self,
+        function_declaration: &str,
+        sandbox: Option<&str>,
+        contexts: Option<Vec<String>>,
+    ) -> Result<(PreloadScript, ChannelReceiver), BrowserError> {
+        let (channel, receiver) =
+            channel::open_channel(&mut self.webdriverbidi_session(), &self.inner.events, &self.inner.channels).await?;
+        let id = preload::add_preload_script(
+            &mut self.webdriverbidi_session(),
+            function_declaration,
+            Some(vec![channel::channel_argument(&channel)]),
+            sandbox,
+            contexts,
+        )
+        .await?;
+        Ok((PreloadScript::new(self.webdriverbidi_session(), id), receiver))
+    }
+
+    /// Removes a preload script by identifier, e.g. one released with `PreloadScript::detach`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.removePreloadScript` command fails.
+    pub async fn remove_preload_script(&self, id: &str) -> Result<(), BrowserError> {
+        preload::remove_preload_script(&mut self.webdriverbidi_session(), id).await
+    }
+}
+
+// --------------------------------------------------
+
+// Exposed functions
+impl Browser {
+    /// Exposes a Rust function to page JavaScript as `window.<name>`.
+    ///
+    /// Calling `window.<name>(...args)` returns a promise resolved with the value returned
+    /// by the handler, which receives the arguments as a JSON array. The function is defined
+    /// in the current document and in every document loaded afterwards.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+    /// browser
+    ///     .expose_function("recordEvent", |args: serde_json::Value| async move {
+    ///         println!("Telemetry: {}", args);
+    ///         serde_json::json!({ "recorded": true })
+    ///     })
+    ///     .await?;
+    /// // Page JavaScript: `const { recorded } = await window.recordEvent("checkout", { total: 42 });`
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::JavaScript` if a function with the same name is already exposed,
+    /// or if the function can't be added to the page.
+    pub async fn expose_function<F, Fut>(&self, name: &str, handler: F) -> Result<(), BrowserError>
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Value> + Send + 'static,
+    {
+        {
+            // Reserved before the page can see the function, so that concurrent calls
+            // from other clones fail instead of defining it twice
+            let mut bindings = lock(&self.inner.bindings);
+            if bindings.contains_key(name) {
+                return Err(BrowserError::JavaScript(format!("The function {} is already exposed", name)));
+            }
+            bindings.insert(name.to_string(), None);
+        }
+        let result = binding::expose_function(
+            &mut self.webdriverbidi_session(),
+            &self.inner.events,
+            &self.inner.channels,
+            self.browsing_context().as_deref(),
+            name,
+            handler,
+        )
+        .await;
+
+        let mut bindings = lock(&self.inner.bindings);
+        match (result, bindings.get_mut(name)) {
+            (Ok(binding), Some(slot)) => {
+                *slot = Some(binding);
+                Ok(())
+            }
+            // The browser was closed or disconnected in the meantime
+            (Ok(_), None) => Err(BrowserError::Disconnected(format!(
+                "The browser was disconnected while exposing {}",
+                name
+            ))),
+            (Err(e), slot) => {
+                if slot.is_some_and(|slot| slot.is_none()) {
+                    bindings.remove(name);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes a function exposed with `expose_function` from the current document and future ones.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::JavaScript` if no function with this name is exposed,
+    /// or if it can't be removed from the current document.
+    pub async fn remove_exposed_function(&self, name: &str) -> Result<(), BrowserError> {
+        {
+            let mut bindings = lock(&self.inner.bindings);
+            match bindings.get(name) {
+                Some(Some(_)) => {
+                    bindings.remove(name);
+                }
+                Some(None) => {
+                    return Err(BrowserError::JavaScript(format!("The function {} is still being exposed", name)));
+                }
+                None => {
+                    return Err(BrowserError::JavaScript(format!("The function {} is not exposed", name)));
+                }
+            }
+        }
+        if let Some(ctx) = self.browsing_context() {
+            binding::remove_from_context(&mut self.webdriverbidi_session(), &ctx, name).await?;
+        }
         Ok(())
     }
 }
@@ -217,12 +1176,221 @@ impl Browser {
     /// Takes a screenshot of the current page and returns the data as a base64-encoded string.
     ///
     /// # Errors
-    /// Returns a `BrowserError::ScreenshotError` if no browsing context is available
-    /// or if taking the screenshot fails.
-    pub async fn take_screenshot(&mut self) -> Result<String, BrowserError> {
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command fails.
+    pub async fn take_screenshot(&self) -> Result<String, BrowserError> {
+        self.take_screenshot_with_options(&ScreenshotOptions::default()).await
+    }
+
+    /// Takes a screenshot of the current page with the given origin, format and clip,
+    /// and returns the data as a base64-encoded string.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, a
+    /// `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command fails,
+    /// or a `BrowserError::ElementNotFound` if the element to clip to is not found.
+    pub async fn take_screenshot_with_options(&self, options: &ScreenshotOptions) -> Result<String, BrowserError> {
+        self.scope().screenshot(options).await
+    }
+
+    /// Takes a PNG screenshot of the whole page and returns the decoded image bytes.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, a
+    /// `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command fails,
+    /// or a `BrowserError::Screenshot` if decoding the screenshot fails.
+    pub async fn screenshot_bytes(&self) -> Result<Vec<u8>, BrowserError> {
+        self.screenshot_bytes_with_options(&ScreenshotOptions::default()).await
+    }
+
+    /// Takes a screenshot with the given options and returns the decoded image bytes.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, a
+    /// `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command fails,
+    /// or a `BrowserError::Screenshot` if decoding the screenshot fails.
+    pub async fn screenshot_bytes_with_options(&self, options: &ScreenshotOptions) -> Result<Vec<u8>, BrowserError> {
+        self.scope().screenshot_bytes(options).await
+    }
+
+    /// Takes a screenshot of the whole page and saves it to a file, creating its parent
+    /// directories. The image format is inferred from the file extension
+    /// (`png`, `jpg`, `jpeg` or `webp`).
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+    /// browser.save_screenshot("target/screenshots/home.png").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, a
+    /// `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command fails,
+    /// or a `BrowserError::Screenshot` if the extension is not supported or if the file
+    /// can't be written.
+    pub async fn save_screenshot(&self, path: impl AsRef<Path>) -> Result<(), BrowserError> {
+        self.save_screenshot_with_options(path, &ScreenshotOptions::default()).await
+    }
+
+    /// Takes a screenshot with the given origin and clip and saves it to a file, creating its
+    /// parent directories. The image format is inferred from the file extension.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, a
+    /// `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command fails,
+    /// or a `BrowserError::Screenshot` if the extension is not supported or if the file
+    /// can't be written.
+    pub async fn save_screenshot_with_options(&self, path: impl AsRef<Path>, options: &ScreenshotOptions) -> Result<(), BrowserError> {
+        self.scope().save_screenshot(path.as_ref(), options).await
+    }
+
+    /// Captures a screenshot and compares it to the baseline image `name`, failing when
+    /// the ratio of differing pixels exceeds the allowed maximum.
+    ///
+    /// Set the `AUTOM8_UPDATE_BASELINES` environment variable to `1` to overwrite the
+    /// baselines with the current screenshots instead.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Assertion` if the baseline is missing, if the sizes differ
+    /// or if too many pixels differ, after writing the actual and diff images to the output
+    /// directory. Returns a `BrowserError::Screenshot` if capturing or file I/O fails.
+    pub async fn assert_screenshot_matches(&self, name: &str, options: &ScreenshotMatchOptions) -> Result<(), BrowserError> {
+        self.scope().assert_screenshot_matches(name, options).await
+    }
+}
+
+// --------------------------------------------------
+
+// Recording
+impl Browser {
+    /// Starts capturing a screenshot of the current browsing context every `interval`
+    /// in a background task, until `stop_recording` is called.
+    ///
+    /// A recording still in progress is saved when the browser is closed or disconnected.
+    /// If the browser is dropped during a panic while recording, e.g. because a test
+    /// assertion failed, the frames captured so far are saved automatically too.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Recording` if a recording is already in progress,
+    /// or a `BrowserError::Navigation` if no browsing context is available.
+    pub fn start_recording(&self, interval: Duration, options: RecordingOptions) -> Result<(), BrowserError> {
         let ctx = self.get_context()?;
-        let data = screenshot::take_screenshot(&mut self.webdriverbidi_session, ctx).await?;
-        Ok(data)
+        let sandbox = self.sandbox();
+        let mut recorder = lock(&self.inner.recorder);
+        if recorder.is_some() {
+            return Err(BrowserError::Recording("A recording is already in progress".to_string()));
+        }
+        *recorder = Some(Recorder::start(&self.webdriverbidi_session(), ctx.as_str(), sandbox.as_deref(), interval, options));
+        Ok(())
+    }
+
+    /// Stops the recording and writes its frames, returning the paths of the written files.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Recording` if no recording is in progress, if no frame
+    /// was captured or if the files can't be written.
+    pub fn stop_recording(&self) -> Result<Vec<PathBuf>, BrowserError> {
+        let recorder = lock(&self.inner.recorder)
+            .take()
+            .ok_or_else(|| BrowserError::Recording("No recording in progress".to_string()))?;
+        recorder.stop()
+    }
+}
+
+// --------------------------------------------------
+
+// Failure artifacts
+impl Browser {
+    /// Sets what is saved when an action of the browser, or of one of its pages or frames,
+    /// fails, e.g. `load`, `evaluate`, `click_element` or `take_screenshot`, or `None` to
+    /// save nothing, the default.
+    ///
+    /// With a policy, the error of a failed action is returned as a
+    /// `BrowserError::WithArtifacts` holding the directory the screenshot, page HTML, URL,
+    /// console entries and failing locator were saved to. Console entries are kept from
+    /// the moment the policy is set.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if subscribing to the console entries fails.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use autom8::ArtifactPolicy;
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+    /// browser.set_artifact_policy(Some(ArtifactPolicy::new("target/artifacts").screenshot(false))).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_artifact_policy(&self, policy: Option<ArtifactPolicy>) -> Result<(), BrowserError> {
+        match policy.as_ref().map(ArtifactPolicy::console_capacity) {
+            Some(capacity) if capacity > 0 => {
+                artifacts::record_console(&mut self.webdriverbidi_session(), &self.inner.events, &self.inner.console, capacity).await?
+            }
+            _ => self.inner.console.stop(),
+        }
+        *lock(&self.inner.artifact_policy) = policy;
+        Ok(())
+    }
+
+    /// Runs an action against `context`, or the current browsing context if `None`, saving
+    /// the failure artifacts of its error if an artifact policy is set.
+    pub(crate) async fn act<T>(
+        &self,
+        context: Option<&str>,
+        sandbox: Option<&str>,
+        action: &str,
+        locator: Option<&str>,
+        future: impl Future<Output = Result<T, BrowserError>>,
+    ) -> Result<T, BrowserError> {
+        let error = match future.await {
+            Err(error) if error.artifacts().is_none() => error,
+            result => return result,
+        };
+        let Some(policy) = lock(&self.inner.artifact_policy).clone() else {
+            return Err(error);
+        };
+
+        // Only the error, locator and console entries can be saved once the browser is gone
+        let context = context
+            .map(str::to_string)
+            .or_else(|| self.browsing_context())
+            .filter(|_| self.is_connected() && !self.inner.closed.load(Ordering::SeqCst));
+        let failure = Failure { action, locator, error: &error };
+        let mut session = self.webdriverbidi_session();
+        match artifacts::capture(&mut session, context.as_deref(), sandbox, &policy, &self.inner.console, &failure).await {
+            Ok(dir) => Err(BrowserError::WithArtifacts { source: Box::new(error), artifacts: dir }),
+            Err(e) => {
+                error!("Saving the failure artifacts of {} failed: {}", action, e);
+                Err(error)
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+
+// PDF printing
+impl Browser {
+    /// Prints the current page to PDF and returns the PDF bytes.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, a
+    /// `BrowserError::Protocol` if the `browsingContext.print` command fails, or a
+    /// `BrowserError::Print` if the PDF can't be decoded.
+    pub async fn print_to_pdf(&self, options: &PrintOptions) -> Result<Vec<u8>, BrowserError> {
+        self.scope().print_to_pdf(options).await
+    }
+
+    /// Prints the current page to a PDF file, creating its parent directories.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, a
+    /// `BrowserError::Protocol` if the `browsingContext.print` command fails, or a
+    /// `BrowserError::Print` if the PDF can't be decoded or the file can't be written.
+    pub async fn save_pdf(&self, path: impl AsRef<Path>, options: &PrintOptions) -> Result<(), BrowserError> {
+        self.scope().save_pdf(path.as_ref(), options).await
     }
 }
 
@@ -239,17 +1407,14 @@ impl Browser {
     ///
     /// # Errors
     ///
-    /// Returns a `BrowserError::LocalStorageError` if no browsing context is available
-    /// or if setting the local storage value fails.
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` if the `script.callFunction` command fails.
     pub async fn set_local_storage_value(
-        &mut self,
+        &self,
         key: &str,
         value: &str,
     ) -> Result<(), BrowserError> {
-        let ctx = self.get_context()?;
-        local_storage::set_local_storage(&mut self.webdriverbidi_session, ctx.as_str(), key, value)
-            .await?;
-        Ok(())
+        self.scope().set_local_storage_value(key, value).await
     }
 
     /// Gets a value from the local storage of the current browsing context.
@@ -260,17 +1425,32 @@ impl Browser {
     ///
     /// # Errors
     ///
-    /// Returns a `BrowserError::LocalStorageError` if no browsing context is available
-    /// or if getting the local storage value fails.
+    /// Returns a `BrowserError::Navigation` if no browsing context is available, or a
+    /// `BrowserError::Protocol` if the `script.callFunction` command fails.
     pub async fn get_local_storage_value(
-        &mut self,
+        &self,
         key: &str,
     ) -> Result<Option<String>, BrowserError> {
-        let ctx = self.get_context()?;
-        local_storage::get_local_storage(&mut self.webdriverbidi_session, ctx.as_str(), key).await
+        self.scope().get_local_storage_value(key).await
     }
 }
 
+// --------------------------------------------------
+
+// Elements
+impl Browser {
+    /// Looks up the element matching a selector, e.g. to take a screenshot of it.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector, or a
+    /// `BrowserError::Navigation` if no browsing context is available.
+    pub async fn element(&self, selector: &str) -> Result<Element, BrowserError> {
+        self.scope().element(selector).await
+    }
+}
+
+// --------------------------------------------------
+
 // Assertions
 impl Browser {
     /// Asserts that an element is present in the current page by checking if it can be selected
@@ -280,11 +1460,10 @@ impl Browser {
     /// - `selector`: The CSS selector of the element to check.
     ///
     /// # Errors
-    /// Returns a `BrowserError::AssertionError` if script evaluation fails.
-    pub async fn assert_element_present(&mut self, selector: &str) -> Result<bool, BrowserError> {
-        let ctx = self.get_context()?;
-        assertions::assert_element_present(&mut self.webdriverbidi_session, ctx.as_str(), selector)
-            .await
+    /// Returns a `BrowserError::Protocol` if the `script.evaluate` command fails, or a
+    /// `BrowserError::ScriptException` if the selector is invalid.
+    pub async fn assert_element_present(&self, selector: &str) -> Result<bool, BrowserError> {
+        self.scope().assert_element_present(selector).await
     }
 }
 
@@ -298,10 +1477,9 @@ impl Browser {
     /// - `selector`: CSS selector to identify the element to click
     ///
     /// # Errors
-    /// Returns a `BrowserError::Action` if the element is not found or clicking fails.
-    pub async fn click_element(&mut self, selector: &str) -> Result<(), BrowserError> {
-        let ctx = self.get_context()?;
-        input::click_element(&mut self.webdriverbidi_session, ctx.as_str(), selector).await
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn click_element(&self, selector: &str) -> Result<(), BrowserError> {
+        self.scope().click_element(selector).await
     }
 
     /// Clicks on an element after waiting for it to become clickable.
@@ -311,10 +1489,9 @@ impl Browser {
     /// - `timeout_ms`: Maximum time to wait for element to be clickable (default: 5000ms)
     ///
     /// # Errors
-    /// Returns a `BrowserError::Action` if the element is not found or doesn't become clickable within timeout.
-    pub async fn wait_and_click_element(&mut self, selector: &str, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
-        let ctx = self.get_context()?;
-        input::wait_and_click_element(&mut self.webdriverbidi_session, ctx.as_str(), selector, timeout_ms).await
+    /// Returns a `BrowserError::Timeout` if the element doesn't become clickable within the timeout.
+    pub async fn wait_and_click_element(&self, selector: &str, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().wait_and_click_element(selector, timeout_ms).await
     }
 
     /// Clicks an element and then waits for page load to complete.
@@ -326,7 +1503,7 @@ impl Browser {
     ///
     /// # Errors
     /// Returns a `BrowserError` if clicking fails or page doesn't load within timeout.
-    pub async fn click_and_wait(&mut self, selector: &str, page_load_timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+    pub async fn click_and_wait(&self, selector: &str, page_load_timeout_ms: Option<u64>) -> Result<(), BrowserError> {
         // Click the element
         self.click_element(selector).await?;
         
@@ -347,13 +1524,15 @@ impl Browser {
     /// - `Err(BrowserError)` if the element was not found or extraction failed
     ///
     /// # Example
-    /// ```rust
+    /// ```rust,no_run
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
     /// let html = browser.extract_inner_html("div.content").await?;
     /// println!("Inner HTML: {}", html);
+    /// # Ok(())
+    /// # }
     /// ```
-    pub async fn extract_inner_html(&mut self, selector: &str) -> Result<String, BrowserError> {
-        let ctx = self.get_context()?;
-        extract::extract_inner_html(&mut self.webdriverbidi_session, ctx.as_str(), selector).await
+    pub async fn extract_inner_html(&self, selector: &str) -> Result<String, BrowserError> {
+        self.scope().extract_inner_html(selector).await
     }
 
     /// Extracts the inner text of an element identified by a CSS selector.
@@ -367,13 +1546,15 @@ impl Browser {
     /// - `Err(BrowserError)` if the element was not found or extraction failed
     ///
     /// # Example
-    /// ```rust
+    /// ```rust,no_run
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
     /// let text = browser.extract_inner_text("h1").await?;
     /// println!("Heading text: {}", text);
+    /// # Ok(())
+    /// # }
     /// ```
-    pub async fn extract_inner_text(&mut self, selector: &str) -> Result<String, BrowserError> {
-        let ctx = self.get_context()?;
-        extract::extract_inner_text(&mut self.webdriverbidi_session, ctx.as_str(), selector).await
+    pub async fn extract_inner_text(&self, selector: &str) -> Result<String, BrowserError> {
+        self.scope().extract_inner_text(selector).await
     }
 
     /// Extracts the value of a specific attribute from an element identified by a CSS selector.
@@ -388,14 +1569,16 @@ impl Browser {
     /// - `Err(BrowserError)` if the element was not found or extraction failed
     ///
     /// # Example
-    /// ```rust
+    /// ```rust,no_run
+    /// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
     /// let href = browser.extract_attribute("a.link", "href").await?;
     /// if let Some(url) = href {
     ///     println!("Link URL: {}", url);
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
-    pub async fn extract_attribute(&mut self, selector: &str, attribute: &str) -> Result<Option<String>, BrowserError> {
-        let ctx = self.get_context()?;
-        extract::extract_attribute(&mut self.webdriverbidi_session, ctx.as_str(), selector, attribute).await
+    pub async fn extract_attribute(&self, selector: &str, attribute: &str) -> Result<Option<String>, BrowserError> {
+        self.scope().extract_attribute(selector, attribute).await
     }
 }
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::{debug, error};
+use serde_json::Value;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use webdriverbidi::model::script::{ChannelProperties, ChannelValue};
+use webdriverbidi::model::session::SubscriptionRequest;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::error::BrowserError;
+use crate::events::EventDispatcher;
+use crate::remote_value;
+
+// --------------------------------------------------
+
+const SCRIPT_MESSAGE: &str = "script.message";
+
+static NEXT_CHANNEL_ID: AtomicU64 = AtomicU64::new(0);
+
+// --------------------------------------------------
+
+/// A message sent by page JavaScript through a BiDi channel.
+#[derive(Debug, Clone)]
+pub struct ChannelMessage {
+    /// The identifier of the channel the message was sent through.
+    pub channel: String,
+    /// The data passed to the channel function, converted to JSON.
+    pub data: Value,
+    /// The realm the message was sent from.
+    pub realm: String,
+    /// The browsing context the message was sent from, if any.
+    pub context: Option<String>,
+}
+
+impl ChannelMessage {
+    /// Parses the parameters of a `script.message` event.
+    fn from_event_params(params: &Value) -> Option<Self> {
+        Some(Self {
+            channel: params.get("channel")?.as_str()?.to_string(),
+            data: remote_value::to_json(params.get("data")?),
+            realm: params.pointer("/source/realm")?.as_str()?.to_string(),
+            context: params
+                .pointer("/source/context")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        })
+    }
+}
+
+/// Receives the messages sent through a channel.
+pub type ChannelReceiver = UnboundedReceiver<ChannelMessage>;
+
+/// Routes `script.message` events to the receivers of their channels.
+#[derive(Default)]
+pub struct ChannelRegistry {
+    senders: Mutex<HashMap<String, UnboundedSender<ChannelMessage>>>,
+    listening: AtomicBool,
+}
+
+impl ChannelRegistry {
+    /// Stops routing the messages of a channel.
+    pub fn close(&self, channel: &str) {
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.remove(channel);
+        }
+    }
+
+    fn dispatch(&self, message: ChannelMessage) {
+        let Ok(mut senders) = self.senders.lock() else {
+            return;
+        };
+        let channel = message.channel.clone();
+        match senders.get(&channel) {
+            Some(sender) => {
+                if sender.send(message).is_err() {
+                    debug!("Receiver of channel {} dropped, closing it", channel);
+                    senders.remove(&channel);
+                }
+            }
+            None => debug!("Ignoring message on unknown channel: {}", channel),
+        }
+    }
+}
+
+// --------------------------------------------------
+
+/// Returns the `script.ChannelValue` argument passing the channel to a function.
+pub fn channel_argument(channel: &str) -> ChannelValue {
+    ChannelValue {
+        value_type: "channel".to_string(),
+        value: ChannelProperties {
+            channel: channel.to_string(),
+            serialization_options: None,
+            ownership: None,
+        },
+    }
+}
+
+/// Creates a channel, subscribing to `script.message` the first time.
+pub async fn open_channel(
+    session: &mut WebDriverBiDiSession,
+    events: &EventDispatcher,
+    registry: &Arc<ChannelRegistry>,
+) -> Result<(String, ChannelReceiver), BrowserError> {
+    if !registry.listening.swap(true, Ordering::SeqCst) {
+        debug!("Listening to script messages");
+        let handler_registry = registry.clone();
+        let handler = events.subscribe(SCRIPT_MESSAGE, move |event| {
+            match event.get("params").and_then(ChannelMessage::from_event_params) {
+                Some(message) => handler_registry.dispatch(message),
+                None => error!("Unexpected script message event: {:?}", event),
+            }
+            true
+        });
+
+        let subscription_params =
+            SubscriptionRequest::new(vec![SCRIPT_MESSAGE.to_string()], None, None);
+        if let Err(e) = session.session_subscribe(subscription_params).await {
+            events.unsubscribe(handler);
+            registry.listening.store(false, Ordering::SeqCst);
+            return Err(BrowserError::command("session.subscribe", e));
+        }
+    }
+
+    let channel = format!(
+        "autom8-channel-{}",
+        NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed)
+    );
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if let Ok(mut senders) = registry.senders.lock() {
+        senders.insert(channel.clone(), sender);
+    }
+    Ok((channel, receiver))
+}
@@ -0,0 +1,31 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{Value, json};
+use webdriverbidi::error::CommandError;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+// Command ids generated here start far above the ones used by webdriverbidi
+// so that both can share the session's pending commands map.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1 << 32);
+
+// --------------------------------------------------
+
+/// Sends a raw WebDriver BiDi command and returns the untyped `result` payload.
+///
+/// This is used where the typed webdriverbidi models lose information, e.g.
+/// `RemoteValue` deserializes window proxies and objects as `NullValue`.
+pub async fn send(
+    session: &mut WebDriverBiDiSession,
+    method: &str,
+    params: Value,
+) -> Result<Value, CommandError> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let command = json!({
+        "id": id,
+        "method": method,
+        "params": params,
+    });
+    session.send_command::<Value, Value>(command).await
+}
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use futures_util::Stream;
+use log::{debug, error};
+use serde_json::{Value, json};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::{Notify, oneshot};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+use webdriverbidi::session::WebDriverBiDiSession;
+use webdriverbidi::webdriver::session::start_session;
+
+// --------------------------------------------------
+
+use crate::command;
+use crate::error::BrowserError;
+use crate::events::EventDispatcher;
+
+// --------------------------------------------------
+
+/// How long a health check waits for the driver to answer.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the connection to the driver is checked.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How many health checks in a row must fail before the connection is considered lost.
+const MAX_FAILED_PINGS: u32 = 3;
+
+/// Returns the id of the session a BiDi WebSocket URL points to, e.g. `abc` for
+/// `ws://localhost:4444/session/abc`.
+fn session_id(ws_url: &str) -> Option<&str> {
+    let path = ws_url.split(['?', '#']).next()?;
+    let (_, rest) = path.rsplit_once("/session/")?;
+    let id = rest.trim_end_matches('/');
+    (!id.is_empty() && !id.contains('/')).then_some(id)
+}
+
+// --------------------------------------------------
+
+type WebSocket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+type PendingCommands = Arc<tokio::sync::Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// Wakes the message loop when the WebSocket has data or was replaced.
+#[derive(Default)]
+struct Wakeup {
+    notify: Notify,
+}
+
+impl Wake for Wakeup {
+    fn wake(self: Arc<Self>) {
+        self.notify.notify_one();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notify.notify_one();
+    }
+}
+
+/// The WebSocket connection of a session, read by a message loop owned by autom8.
+///
+/// The loop resolves command responses through the pending commands table of the
+/// session and queues events for a dispatch task, so reading the connection never waits
+/// for event subscribers. Both tasks stop when the last handle is dropped.
+#[derive(Clone)]
+pub struct Connection {
+    inner: Arc<ConnectionTasks>,
+}
+
+struct ConnectionTasks {
+    wakeup: Arc<Wakeup>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for ConnectionTasks {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl Connection {
+    /// Connects the session to its WebSocket URL and starts reading it, flagging the
+    /// connection as `lost` when the driver closes it.
+    pub async fn open(
+        session: &mut WebDriverBiDiSession,
+        events: Arc<EventDispatcher>,
+        lost: Arc<AtomicBool>,
+    ) -> Result<Self, BrowserError> {
+        debug!("Establishing the WebSocket connection to {}", session.websocket_url);
+        let (stream, _) = connect_async(session.websocket_url.as_str()).await.map_err(|e| {
+            BrowserError::SessionCreation(format!("Connecting to {} failed: {}", session.websocket_url, e))
+        })?;
+        let stream = Arc::new(tokio::sync::Mutex::new(stream));
+        session.websocket_stream = Some(stream.clone());
+
+        let wakeup = Arc::new(Wakeup::default());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let read = tokio::spawn(read_messages(
+            stream,
+            session.pending_commands.clone(),
+            sender,
+            wakeup.clone(),
+            lost,
+        ));
+        let dispatch = tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                events.dispatch(&event);
+            }
+        });
+        Ok(Self {
+            inner: Arc::new(ConnectionTasks {
+                wakeup,
+                tasks: vec![read, dispatch],
+            }),
+        })
+    }
+
+    /// Makes the message loop read the stream again, e.g. after it was replaced.
+    fn resume(&self) {
+        self.inner.wakeup.notify.notify_one();
+    }
+}
+
+/// Reads the WebSocket until the connection is dropped.
+///
+/// The stream is shared with the command senders of the session, so it is only locked
+/// while polled: the loop then waits to be woken by the stream, or by `resume` once the
+/// connection was closed and reopened.
+async fn read_messages(
+    stream: Arc<tokio::sync::Mutex<WebSocket>>,
+    pending_commands: PendingCommands,
+    events: UnboundedSender<Value>,
+    wakeup: Arc<Wakeup>,
+    lost: Arc<AtomicBool>,
+) {
+    let waker = Waker::from(wakeup.clone());
+    loop {
+        let message = {
+            let mut stream = stream.lock().await;
+            Pin::new(&mut *stream).poll_next(&mut Context::from_waker(&waker))
+        };
+        match message {
+            Poll::Pending => wakeup.notify.notified().await,
+            Poll::Ready(Some(Ok(Message::Text(text)))) => {
+                route_message(text.as_str(), &pending_commands, &events).await
+            }
+            Poll::Ready(Some(Ok(Message::Close(_)))) | Poll::Ready(Some(Err(_))) | Poll::Ready(None) => {
+                debug!("The WebSocket connection was closed");
+                lost.store(true, Ordering::SeqCst);
+                // The driver won't answer the commands in flight anymore
+                pending_commands.lock().await.clear();
+                wakeup.notify.notified().await;
+            }
+            Poll::Ready(Some(Ok(_))) => {}
+        }
+    }
+}
+
+/// Hands a command response to its sender, or queues an event for dispatch.
+async fn route_message(text: &str, pending_commands: &PendingCommands, events: &UnboundedSender<Value>) {
+    let message: Value = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => {
+            error!("Received a message that isn't JSON: {}", e);
+            return;
+        }
+    };
+    if let Some(id) = message.get("id").and_then(Value::as_u64) {
+        if let Some(sender) = pending_commands.lock().await.remove(&id) {
+            let _ = sender.send(message);
+        }
+    } else if message.get("type").and_then(Value::as_str) == Some("event") {
+        let _ = events.send(message);
+    } else {
+        error!("Received a message that is neither a response nor an event: {}", text);
+    }
+}
+
+/// Creates a new session over HTTP, then connects to its WebSocket.
+pub async fn start(
+    session: &mut WebDriverBiDiSession,
+    events: Arc<EventDispatcher>,
+    lost: Arc<AtomicBool>,
+) -> Result<Connection, BrowserError> {
+    let response = start_session(&session.base_url, &session.capabilities)
+        .await
+        .map_err(|e| BrowserError::SessionCreation(format!("Starting the WebDriverBiDi session failed: {}", e)))?;
+    session.session_id = response.session_id;
+    session.websocket_url = response.websocket_url;
+    Connection::open(session, events, lost).await
+}
+
+/// Connects to an existing session through its BiDi WebSocket URL, without creating a new one.
+pub async fn attach(
+    session: &mut WebDriverBiDiSession,
+    ws_url: &str,
+    events: Arc<EventDispatcher>,
+    lost: Arc<AtomicBool>,
+) -> Result<Connection, BrowserError> {
+    if !ws_url.starts_with("ws://") && !ws_url.starts_with("wss://") {
+        return Err(BrowserError::SessionCreation(format!(
+            "{} is not a WebSocket URL",
+            ws_url
+        )));
+    }
+    let session_id = session_id(ws_url).ok_or_else(|| {
+        BrowserError::SessionCreation(format!(
+            "{} doesn't point to a session, expected a URL ending with /session/<id>",
+            ws_url
+        ))
+    })?;
+
+    debug!("Connecting to the session {} at {}", session_id, ws_url);
+    session.session_id = session_id.to_string();
+    session.websocket_url = ws_url.to_string();
+    Connection::open(session, events, lost).await
+}
+
+/// Closes the WebSocket connection, leaving the session and the browser running.
+///
+/// The stream is closed in place, so that `reconnect` can reopen it for every clone
+/// of the session.
+pub async fn disconnect(session: &mut WebDriverBiDiSession) -> Result<(), BrowserError> {
+    let Some(stream) = &session.websocket_stream else {
+        return Ok(());
+    };
+    stream.lock().await.close(None).await.map_err(|e| {
+        BrowserError::SessionClosing(format!("Closing the WebSocket connection failed: {}", e))
+    })
+}
+
+/// Closes an attached session's browser over BiDi, since there is no HTTP endpoint to
+/// end it through.
+pub async fn close_browser(session: &mut WebDriverBiDiSession) -> Result<(), BrowserError> {
+    command::send(session, "browser.close", json!({}))
+        .await
+        .map_err(|e| BrowserError::command("browser.close", e))?;
+    Ok(())
+}
+
+// --------------------------------------------------
+
+/// How a `Browser` reconnects after losing its connection to the driver. Attempts are
+/// spaced by a delay doubling from `initial_delay` up to `max_delay`.
+///
+/// # Example
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use autom8::ReconnectPolicy;
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let policy = ReconnectPolicy::new()
+///     .max_attempts(10)
+///     .initial_delay(Duration::from_millis(500));
+/// browser.set_reconnect_policy(Some(policy));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    max_attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Creates the default policy: 5 attempts, waiting 200 milliseconds after the first
+    /// failure and at most 5 seconds between attempts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of connection attempts before giving up.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay after the first failed attempt.
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the longest delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+}
+
+// --------------------------------------------------
+
+/// Checks that the driver answers a `session.status` command.
+pub async fn ping(session: &mut WebDriverBiDiSession) -> Result<(), BrowserError> {
+    let status = command::send(session, "session.status", json!({}));
+    match tokio::time::timeout(PING_TIMEOUT, status).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(BrowserError::Disconnected(format!(
+            "The session.status command failed: {}",
+            e
+        ))),
+        Err(_) => Err(BrowserError::Disconnected(format!(
+            "The driver didn't answer within {} milliseconds",
+            PING_TIMEOUT.as_millis()
+        ))),
+    }
+}
+
+/// Reopens the WebSocket connection of the session, retrying with backoff.
+///
+/// The new stream replaces the old one in place, so every clone of the session, the
+/// message loop and the event subscribers keep working with it.
+pub async fn reconnect(
+    session: &mut WebDriverBiDiSession,
+    connection: &Connection,
+    policy: &ReconnectPolicy,
+) -> Result<(), BrowserError> {
+    let stream = session.websocket_stream.clone().ok_or_else(|| {
+        BrowserError::Disconnected("The session was never connected".to_string())
+    })?;
+    let url = session.websocket_url.clone();
+    let mut delay = policy.initial_delay;
+    let mut last_error = String::new();
+    for attempt in 1..=policy.max_attempts {
+        debug!("Reconnecting to {} (attempt {}/{})", url, attempt, policy.max_attempts);
+        match connect_async(url.as_str()).await {
+            Ok((new_stream, _)) => {
+                *stream.lock().await = new_stream;
+                connection.resume();
+                match ping(session).await {
+                    Ok(()) => {
+                        debug!("Reconnected to {}", url);
+                        return Ok(());
+                    }
+                    Err(e) => last_error = e.to_string(),
+                }
+            }
+            Err(e) => last_error = e.to_string(),
+        }
+        if attempt < policy.max_attempts {
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(policy.max_delay);
+        }
+    }
+    Err(BrowserError::Disconnected(format!(
+        "Reconnecting to {} failed after {} attempts: {}",
+        url, policy.max_attempts, last_error
+    )))
+}
+
+/// Pings the driver in the background, flagging the connection as lost once it stops
+/// answering. Aborted when dropped, and restarted by `Browser::reconnect`.
+pub struct Watchdog {
+    task: JoinHandle<()>,
+}
+
+impl Watchdog {
+    pub fn start(session: &WebDriverBiDiSession, lost: Arc<AtomicBool>) -> Self {
+        let session = session.clone();
+        let task = tokio::spawn(async move {
+            let ping_session = session.clone();
+            let e = health_checks(HEALTH_CHECK_INTERVAL, || {
+                let mut session = ping_session.clone();
+                async move { ping(&mut session).await }
+            })
+            .await;
+            error!("Lost the connection to the driver: {}", e);
+            lost.store(true, Ordering::SeqCst);
+            // Fail the commands awaiting a response instead of letting them time out
+            session.pending_commands.lock().await.clear();
+        });
+        Self { task }
+    }
+}
+
+/// Calls `ping` every `interval` until `MAX_FAILED_PINGS` calls in a row fail, then
+/// returns the last error. A driver busy for a moment misses a check without the
+/// connection being considered lost.
+async fn health_checks<F, Fut>(interval: Duration, mut ping: F) -> BrowserError
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), BrowserError>>,
+{
+    let mut failures = 0;
+    loop {
+        tokio::time::sleep(interval).await;
+        match ping().await {
+            Ok(()) => failures = 0,
+            Err(e) => {
+                failures += 1;
+                if failures >= MAX_FAILED_PINGS {
+                    return e;
+                }
+                debug!("Health check failed ({}/{}): {}", failures, MAX_FAILED_PINGS, e);
+            }
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn dropped() -> BrowserError {
+        BrowserError::Disconnected("The driver didn't answer".to_string())
+    }
+
+    #[test]
+    fn one_dropped_ping_keeps_the_connection() {
+        // Only the first ping is dropped; the connection is lost from the 10th ping on
+        let pings = Cell::new(0);
+        let error = block_on(health_checks(Duration::from_millis(1), || {
+            pings.set(pings.get() + 1);
+            let ping = pings.get();
+            async move { if ping == 1 || ping >= 10 { Err(dropped()) } else { Ok(()) } }
+        }));
+        assert!(matches!(error, BrowserError::Disconnected(_)));
+        assert_eq!(pings.get(), 9 + MAX_FAILED_PINGS);
+    }
+
+    #[test]
+    fn consecutive_dropped_pings_lose_the_connection() {
+        let pings = Cell::new(0);
+        block_on(health_checks(Duration::from_millis(1), || {
+            pings.set(pings.get() + 1);
+            async { Err(dropped()) }
+        }));
+        assert_eq!(pings.get(), MAX_FAILED_PINGS);
+    }
+
+    #[test]
+    fn session_id_is_read_from_the_url() {
+        assert_eq!(session_id("ws://localhost:4444/session/abc"), Some("abc"));
+        assert_eq!(session_id("ws://localhost:4444/session/abc/?x=1"), Some("abc"));
+        assert_eq!(session_id("ws://localhost:4444/"), None);
+    }
+}
@@ -0,0 +1,109 @@
+/// A device preset combining viewport size, device pixel ratio, user agent and touch support.
+///
+/// Presets can be applied with `Browser::emulate_device`, or with an `Emulation`
+/// handle to target another browsing context or a whole user context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    /// The name of the preset, e.g. `"iPhone 15"`.
+    pub name: &'static str,
+    /// The viewport width in CSS pixels.
+    pub width: u64,
+    /// The viewport height in CSS pixels.
+    pub height: u64,
+    /// The ratio between physical and CSS pixels.
+    pub device_pixel_ratio: f32,
+    /// The user agent reported by the device, or `None` to keep the browser's own.
+    pub user_agent: Option<&'static str>,
+    /// Whether the device has a touch screen.
+    pub has_touch: bool,
+}
+
+impl Device {
+    pub const IPHONE_SE: Device = Device {
+        name: "iPhone SE",
+        width: 375,
+        height: 667,
+        device_pixel_ratio: 2.0,
+        user_agent: Some(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+        ),
+        has_touch: true,
+    };
+
+    pub const IPHONE_15: Device = Device {
+        name: "iPhone 15",
+        width: 393,
+        height: 852,
+        device_pixel_ratio: 3.0,
+        user_agent: Some(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+        ),
+        has_touch: true,
+    };
+
+    pub const PIXEL_7: Device = Device {
+        name: "Pixel 7",
+        width: 412,
+        height: 915,
+        device_pixel_ratio: 2.625,
+        user_agent: Some(
+            "Mozilla/5.0 (Linux; Android 14; Pixel 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+        ),
+        has_touch: true,
+    };
+
+    pub const IPAD: Device = Device {
+        name: "iPad",
+        width: 810,
+        height: 1080,
+        device_pixel_ratio: 2.0,
+        user_agent: Some(
+            "Mozilla/5.0 (iPad; CPU OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+        ),
+        has_touch: true,
+    };
+
+    pub const DESKTOP_HD: Device = Device {
+        name: "Desktop HD",
+        width: 1280,
+        height: 720,
+        device_pixel_ratio: 1.0,
+        user_agent: None,
+        has_touch: false,
+    };
+
+    pub const DESKTOP_FULL_HD: Device = Device {
+        name: "Desktop Full HD",
+        width: 1920,
+        height: 1080,
+        device_pixel_ratio: 1.0,
+        user_agent: None,
+        has_touch: false,
+    };
+
+    /// All the built-in presets.
+    pub const ALL: &'static [Device] = &[
+        Device::IPHONE_SE,
+        Device::IPHONE_15,
+        Device::PIXEL_7,
+        Device::IPAD,
+        Device::DESKTOP_HD,
+        Device::DESKTOP_FULL_HD,
+    ];
+
+    /// Returns the built-in preset with the given name, ignoring case.
+    pub fn find(name: &str) -> Option<&'static Device> {
+        Device::ALL
+            .iter()
+            .find(|device| device.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the device rotated to landscape orientation.
+    pub fn landscape(&self) -> Device {
+        Device {
+            width: self.width.max(self.height),
+            height: self.width.min(self.height),
+            ..self.clone()
+        }
+    }
+}
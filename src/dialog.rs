@@ -0,0 +1,235 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use log::{debug, error};
+use serde_json::{Value, json};
+use webdriverbidi::model::session::SubscriptionRequest;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::browser::lock;
+use crate::command;
+use crate::error::BrowserError;
+use crate::events::{EventDispatcher, SubscriberId};
+
+// --------------------------------------------------
+
+const USER_PROMPT_OPENED: &str = "browsingContext.userPromptOpened";
+
+// --------------------------------------------------
+
+/// The kind of a JavaScript dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogType {
+    Alert,
+    BeforeUnload,
+    Confirm,
+    Prompt,
+}
+
+impl DialogType {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "alert" => Some(Self::Alert),
+            "beforeunload" => Some(Self::BeforeUnload),
+            "confirm" => Some(Self::Confirm),
+            "prompt" => Some(Self::Prompt),
+            _ => None,
+        }
+    }
+}
+
+/// A JavaScript dialog opened by a page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialog {
+    /// The browsing context that opened the dialog.
+    pub context: String,
+    /// The kind of the dialog.
+    pub dialog_type: DialogType,
+    /// The message displayed by the dialog.
+    pub message: String,
+    /// The default value of a `prompt` dialog.
+    pub default_value: Option<String>,
+}
+
+impl Dialog {
+    /// Parses the parameters of a `browsingContext.userPromptOpened` event.
+    fn from_event_params(params: &Value) -> Option<Self> {
+        Some(Self {
+            context: params.get("context")?.as_str()?.to_string(),
+            dialog_type: DialogType::from_str(params.get("type")?.as_str()?)?,
+            message: params
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            default_value: params
+                .get("defaultValue")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        })
+    }
+}
+
+/// How a dialog is closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DialogAction {
+    /// Clicks "OK".
+    Accept,
+    /// Clicks "Cancel".
+    Dismiss,
+    /// Types the text into a `prompt` dialog and clicks "OK".
+    Reply(String),
+}
+
+/// An async callback deciding how to close a dialog.
+pub type DialogCallback =
+    Arc<dyn Fn(Dialog) -> Pin<Box<dyn Future<Output = DialogAction> + Send>> + Send + Sync>;
+
+/// The policy applied to every dialog opened while it is registered.
+#[derive(Clone)]
+pub enum DialogPolicy {
+    /// Accepts every dialog.
+    Accept,
+    /// Dismisses every dialog.
+    Dismiss,
+    /// Accepts every dialog, typing the text into `prompt` dialogs.
+    Reply(String),
+    /// Lets a callback receiving the dialog decide.
+    Callback(DialogCallback),
+}
+
+impl DialogPolicy {
+    /// Creates a `DialogPolicy::Callback` from an async closure.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use autom8::{DialogAction, DialogPolicy, DialogType};
+    ///
+    /// let policy = DialogPolicy::callback(|dialog| async move {
+    ///     if dialog.dialog_type == DialogType::Confirm && dialog.message.contains("delete") {
+    ///         DialogAction::Dismiss
+    ///     } else {
+    ///         DialogAction::Accept
+    ///     }
+    /// });
+    /// ```
+    pub fn callback<F, Fut>(callback: F) -> Self
+    where
+        F: Fn(Dialog) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = DialogAction> + Send + 'static,
+    {
+        Self::Callback(Arc::new(move |dialog| Box::pin(callback(dialog))))
+    }
+
+    async fn action_for(&self, dialog: Dialog) -> DialogAction {
+        match self {
+            Self::Accept => DialogAction::Accept,
+            Self::Dismiss => DialogAction::Dismiss,
+            Self::Reply(text) => DialogAction::Reply(text.clone()),
+            Self::Callback(callback) => callback(dialog).await,
+        }
+    }
+}
+
+// --------------------------------------------------
+
+/// Closes the dialog currently open in the given context.
+pub async fn handle_dialog(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    action: DialogAction,
+) -> Result<(), BrowserError> {
+    // Sent raw: webdriverbidi serializes `userText` as `user_text`
+    let params = match action {
+        DialogAction::Accept => json!({ "context": context, "accept": true }),
+        DialogAction::Dismiss => json!({ "context": context, "accept": false }),
+        DialogAction::Reply(text) => json!({ "context": context, "accept": true, "userText": text }),
+    };
+    command::send(session, "browsingContext.handleUserPrompt", params)
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.handleUserPrompt", e))?;
+    Ok(())
+}
+
+/// The dialog policy of a browser and the last dialog it closed.
+#[derive(Default)]
+pub struct DialogRegistry {
+    policy: Mutex<Option<DialogPolicy>>,
+    last_dialog: Mutex<Option<Dialog>>,
+    // The dispatcher subscriber closing the dialogs while a policy is set
+    handler: Mutex<Option<SubscriberId>>,
+}
+
+impl DialogRegistry {
+    /// Returns the last dialog opened while a policy was set.
+    pub fn last_dialog(&self) -> Option<Dialog> {
+        lock(&self.last_dialog).clone()
+    }
+
+    /// Records the dialog of a `browsingContext.userPromptOpened` event and closes it
+    /// according to the policy, if one is set.
+    fn on_prompt_opened(&self, session: &WebDriverBiDiSession, event: &Value) {
+        let Some(policy) = lock(&self.policy).clone() else {
+            return;
+        };
+        let Some(dialog) = event.get("params").and_then(Dialog::from_event_params) else {
+            error!("Unexpected user prompt event: {:?}", event);
+            return;
+        };
+        debug!("Dialog opened: {:?}", dialog);
+        *lock(&self.last_dialog) = Some(dialog.clone());
+        // The policy runs on a task of its own, so a slow callback doesn't delay other events
+        let mut session = session.clone();
+        tokio::spawn(async move {
+            let context = dialog.context.clone();
+            let action = policy.action_for(dialog).await;
+            debug!("Closing dialog with action: {:?}", action);
+            if let Err(e) = handle_dialog(&mut session, &context, action).await {
+                error!("{}", e);
+            }
+        });
+    }
+}
+
+/// Subscribes to `browsingContext.userPromptOpened` and closes every dialog
+/// according to the policy, recording it as the last dialog. The policy is applied
+/// on a spawned task, so a slow callback doesn't delay other events.
+pub async fn set_dialog_policy(
+    session: &mut WebDriverBiDiSession,
+    events: &EventDispatcher,
+    registry: &Arc<DialogRegistry>,
+    policy: DialogPolicy,
+) -> Result<(), BrowserError> {
+    *lock(&registry.policy) = Some(policy);
+    {
+        let mut handler = lock(&registry.handler);
+        if handler.is_none() {
+            let handler_session = session.clone();
+            let handler_registry = registry.clone();
+            *handler = Some(events.subscribe(USER_PROMPT_OPENED, move |event| {
+                handler_registry.on_prompt_opened(&handler_session, event);
+                true
+            }));
+        }
+    }
+
+    let subscription_params =
+        SubscriptionRequest::new(vec![USER_PROMPT_OPENED.to_string()], None, None);
+    if let Err(e) = session.session_subscribe(subscription_params).await {
+        clear_dialog_policy(events, registry);
+        return Err(BrowserError::command("session.subscribe", e));
+    }
+    Ok(())
+}
+
+/// Removes the dialog policy and its handler. The subscription to
+/// `browsingContext.userPromptOpened` is kept for the other subscribers of the session.
+pub fn clear_dialog_policy(events: &EventDispatcher, registry: &DialogRegistry) {
+    *lock(&registry.policy) = None;
+    if let Some(handler) = lock(&registry.handler).take() {
+        events.unsubscribe(handler);
+    }
+}
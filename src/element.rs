@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use log::debug;
+use serde_json::{Value, json};
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::command;
+use crate::error::BrowserError;
+use crate::screenshot::{self, ScreenshotOptions};
+use crate::{files, frame, script, selector};
+
+// --------------------------------------------------
+
+/// Returns the shared id of the element matching a selector in the given context.
+pub async fn shared_id(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    selector: &str,
+) -> Result<String, BrowserError> {
+    debug!("Looking up element with selector: {}", selector);
+    let params = json!({
+        "expression": selector::query_selector(selector),
+        "target": script::context_target(context, sandbox),
+        "awaitPromise": false,
+        "serializationOptions": { "maxDomDepth": 0 },
+    });
+    let result = command::send(session, "script.evaluate", params)
+        .await
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
+
+    match result.get("type").and_then(Value::as_str) {
+        Some("success") => result
+            .pointer("/result/sharedId")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| BrowserError::ElementNotFound {
+                locator: selector.to_string(),
+            }),
+        Some("exception") => Err(script::exception_error(&result)),
+        _ => Err(BrowserError::Element(
+            "Unexpected result from element lookup script".to_string(),
+        )),
+    }
+}
+
+/// Looks up the element matching a (frame-piercing) selector.
+pub async fn find_element(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    selector: &str,
+) -> Result<Element, BrowserError> {
+    let (context, element_selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
+    let shared_id = shared_id(session, &context, sandbox, &element_selector)
+        .await
+        .map_err(|e| e.for_locator(selector))?;
+    Ok(Element {
+        session: session.clone(),
+        context,
+        sandbox: sandbox.map(str::to_string),
+        shared_id,
+    })
+}
+
+// --------------------------------------------------
+
+/// A reference to a DOM element, resolved once when it was looked up.
+///
+/// The reference becomes stale if the element is removed from the document.
+pub struct Element {
+    session: WebDriverBiDiSession,
+    context: String,
+    // The sandbox realm the element was looked up in, running the screenshot scripts
+    sandbox: Option<String>,
+    shared_id: String,
+}
+
+impl Element {
+    /// Returns the browsing context of the document containing the element.
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Returns the shared id referencing the element in WebDriver BiDi commands.
+    pub fn shared_id(&self) -> &str {
+        &self.shared_id
+    }
+
+    /// Takes a PNG screenshot of the element and returns the data as a base64-encoded string.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if taking the screenshot fails, e.g. because the
+    /// element is stale.
+    pub async fn screenshot(&self) -> Result<String, BrowserError> {
+        self.screenshot_with_options(&ScreenshotOptions::new()).await
+    }
+
+    /// Takes a screenshot of the element with the origin and format of `options`,
+    /// ignoring their clip. Elements of frames are captured from their top-level page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if taking the screenshot fails.
+    pub async fn screenshot_with_options(&self, options: &ScreenshotOptions) -> Result<String, BrowserError> {
+        let mut session = self.session.clone();
+        let context = frame::top_level_context(&mut session, &self.context).await?;
+        let options = options.clone().shared_id(&self.context, &self.shared_id);
+        screenshot::take_screenshot(&mut session, context, self.sandbox.as_deref(), &options).await
+    }
+
+    /// Takes a PNG screenshot of the element and returns the decoded image bytes.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if taking the screenshot fails, or a
+    /// `BrowserError::Screenshot` if decoding it fails.
+    pub async fn screenshot_bytes(&self) -> Result<Vec<u8>, BrowserError> {
+        let data = self.screenshot().await?;
+        screenshot::decode(&data)
+    }
+
+    /// Takes a screenshot of the element and saves it to a file, creating its parent
+    /// directories. The image format is inferred from the file extension.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Screenshot` if the extension is not supported, if taking the
+    /// screenshot fails or if the file can't be written.
+    pub async fn save_screenshot(&self, path: impl AsRef<Path>) -> Result<(), BrowserError> {
+        let path = path.as_ref();
+        let options = screenshot::options_for_path(path, &ScreenshotOptions::new())?;
+        let data = self.screenshot_with_options(&options).await?;
+        files::write_file(path, &screenshot::decode(&data)?, BrowserError::Screenshot)
+    }
+}
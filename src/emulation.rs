@@ -0,0 +1,570 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+use serde_json::{Map, Value, json};
+use webdriverbidi::error::CommandError;
+use webdriverbidi::model::browsing_context::{SetViewportParameters, Viewport};
+use webdriverbidi::model::emulation::{
+    GeolocationCoordinates, SetGeolocationOverrideParameters, SetLocaleOverrideParameters,
+    SetTimezoneOverrideParameters,
+};
+use webdriverbidi::model::script::{
+    AddPreloadScriptParameters, CallFunctionParameters, ContextTarget,
+    RemovePreloadScriptParameters, Target,
+};
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::browser::Browser;
+use crate::command;
+use crate::device::Device;
+use crate::error::BrowserError;
+use crate::scope::ContextScope;
+
+// --------------------------------------------------
+
+/// The identifier of the user context every session starts with.
+pub const DEFAULT_USER_CONTEXT: &str = "default";
+
+/// Preload script shims installed by emulation settings, keyed by scope and feature.
+pub type ShimRegistry = Arc<Mutex<HashMap<String, String>>>;
+
+// --------------------------------------------------
+
+/// The browsing contexts an emulation setting applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmulationScope {
+    /// A single top-level browsing context.
+    Context(String),
+    /// Every browsing context, current and future, of a user context.
+    UserContext(String),
+}
+
+impl EmulationScope {
+    /// Returns the scope covering every page of the default user context.
+    pub fn default_user_context() -> Self {
+        Self::UserContext(DEFAULT_USER_CONTEXT.to_string())
+    }
+
+    /// Returns the `contexts` and `userContexts` parameters of typed commands.
+    fn targets(&self) -> (Option<Vec<String>>, Option<Vec<String>>) {
+        match self {
+            Self::Context(context) => (Some(vec![context.clone()]), None),
+            Self::UserContext(user_context) => (None, Some(vec![user_context.clone()])),
+        }
+    }
+
+    /// Returns the `contexts` or `userContexts` parameter of `emulation.*` commands.
+    fn params(&self) -> Map<String, Value> {
+        let mut params = Map::new();
+        match self {
+            Self::Context(context) => params.insert("contexts".into(), json!([context])),
+            Self::UserContext(user_context) => {
+                params.insert("userContexts".into(), json!([user_context]))
+            }
+        };
+        params
+    }
+}
+
+/// The value of the `prefers-color-scheme` media feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+        }
+    }
+}
+
+/// The value of the `prefers-reduced-motion` media feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReducedMotion {
+    Reduce,
+    NoPreference,
+}
+
+impl ReducedMotion {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reduce => "reduce",
+            Self::NoPreference => "no-preference",
+        }
+    }
+}
+
+// --------------------------------------------------
+
+/// Returns a preload function making `window.matchMedia` report the value of a media feature.
+///
+/// `window.matchMedia` is wrapped once per document; the original function and the
+/// overridden features are kept in `window.__autom8MediaFeatures`, shared by the shims.
+fn media_feature_shim(feature: &str, value: &str) -> String {
+    format!(
+        r#"
+        () => {{
+            if (!window.__autom8MediaFeatures) {{
+                const state = {{ original: window.matchMedia.bind(window), overrides: {{}} }};
+                Object.defineProperty(window, "__autom8MediaFeatures", {{ value: state }});
+                window.matchMedia = (query) => {{
+                    const list = state.original(query);
+                    for (const [feature, value] of Object.entries(state.overrides)) {{
+                        const pattern = new RegExp("\\(\\s*" + feature + "\\s*:\\s*([a-z-]+)\\s*\\)");
+                        const match = String(query).match(pattern);
+                        if (match) {{
+                            const matches = match[1] === value;
+                            Object.defineProperty(list, "matches", {{ get: () => matches, configurable: true }});
+                        }}
+                    }}
+                    return list;
+                }};
+            }}
+            window.__autom8MediaFeatures.overrides[{}] = {};
+        }}
+        "#,
+        Value::from(feature),
+        Value::from(value)
+    )
+}
+
+/// A preload function making the page believe the network is unreachable.
+const OFFLINE_SHIM: &str = r#"
+    () => {
+        Object.defineProperty(Navigator.prototype, "onLine", { get: () => false, configurable: true });
+        window.fetch = () => Promise.reject(new TypeError("NetworkError: offline emulation"));
+        XMLHttpRequest.prototype.send = function () {
+            setTimeout(() => this.dispatchEvent(new ProgressEvent("error")));
+        };
+        window.WebSocket = class {
+            constructor() {
+                throw new DOMException("offline emulation", "NetworkError");
+            }
+        };
+        window.dispatchEvent(new Event("offline"));
+    }
+"#;
+
+/// Returns whether a command failed because the remote end doesn't implement it.
+fn is_unknown_command(error: &CommandError) -> bool {
+    matches!(error, CommandError::Error(response)
+        if response.get("error").and_then(Value::as_str) == Some("unknown command"))
+}
+
+// --------------------------------------------------
+
+/// Sends an `emulation.*` command with the given parameters, scoped to the contexts.
+async fn send_emulation_command(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    method: &str,
+    params: Map<String, Value>,
+) -> Result<(), BrowserError> {
+    let mut scoped_params = scope.params();
+    scoped_params.extend(params);
+    debug!("Sending {} with params: {:?}", method, scoped_params);
+    command::send(session, method, Value::Object(scoped_params))
+        .await
+        .map_err(|e| BrowserError::command(method, e))?;
+    Ok(())
+}
+
+/// Sets the viewport size and, optionally, the device pixel ratio.
+pub async fn set_viewport(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    width: u64,
+    height: u64,
+    device_pixel_ratio: Option<f32>,
+) -> Result<(), BrowserError> {
+    let (contexts, user_contexts) = scope.targets();
+    let set_viewport_params = SetViewportParameters::new(
+        contexts.and_then(|contexts| contexts.into_iter().next()),
+        Some(Viewport::new(width, height)),
+        device_pixel_ratio,
+        user_contexts,
+    );
+    session
+        .browsing_context_set_viewport(set_viewport_params)
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.setViewport", e))?;
+    Ok(())
+}
+
+/// Restores the default viewport size and device pixel ratio.
+pub async fn reset_viewport(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+) -> Result<(), BrowserError> {
+    // Sent raw: webdriverbidi skips `None` fields instead of sending `null`
+    let params = match scope {
+        EmulationScope::Context(context) => {
+            json!({ "context": context, "viewport": null, "devicePixelRatio": null })
+        }
+        EmulationScope::UserContext(user_context) => {
+            json!({ "userContexts": [user_context], "viewport": null, "devicePixelRatio": null })
+        }
+    };
+    command::send(session, "browsingContext.setViewport", params)
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.setViewport", e))?;
+    Ok(())
+}
+
+/// Overrides the user agent, or restores the browser's own when `None`.
+pub async fn set_user_agent(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    user_agent: Option<&str>,
+) -> Result<(), BrowserError> {
+    let mut params = Map::new();
+    params.insert("userAgent".into(), json!(user_agent));
+    send_emulation_command(session, scope, "emulation.setUserAgentOverride", params).await
+}
+
+/// Overrides the number of supported touch points, or restores the default when `None`.
+pub async fn set_touch(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    max_touch_points: Option<u32>,
+) -> Result<(), BrowserError> {
+    let mut params = Map::new();
+    params.insert("maxTouchPoints".into(), json!(max_touch_points));
+    send_emulation_command(session, scope, "emulation.setTouchOverride", params).await
+}
+
+/// Applies the viewport, device pixel ratio, user agent and touch support of a device.
+pub async fn emulate_device(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    device: &Device,
+) -> Result<(), BrowserError> {
+    debug!("Emulating device: {}", device.name);
+    set_viewport(
+        session,
+        scope,
+        device.width,
+        device.height,
+        Some(device.device_pixel_ratio),
+    )
+    .await?;
+    set_user_agent(session, scope, device.user_agent).await?;
+    set_touch(session, scope, device.has_touch.then_some(5)).await?;
+    Ok(())
+}
+
+/// Overrides the geolocation position, or removes the override when `None`.
+pub async fn set_geolocation(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    coordinates: Option<GeolocationCoordinates>,
+) -> Result<(), BrowserError> {
+    let (contexts, user_contexts) = scope.targets();
+    let params = SetGeolocationOverrideParameters::with_coordinates(coordinates, contexts, user_contexts);
+    session
+        .set_geolocation_override(params)
+        .await
+        .map_err(|e| BrowserError::command("emulation.setGeolocationOverride", e))?;
+    Ok(())
+}
+
+/// Overrides the locale, e.g. `"de-DE"`, or removes the override when `None`.
+pub async fn set_locale(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    locale: Option<&str>,
+) -> Result<(), BrowserError> {
+    let (contexts, user_contexts) = scope.targets();
+    let params = SetLocaleOverrideParameters {
+        locale: locale.map(str::to_string),
+        contexts,
+        user_contexts,
+    };
+    session
+        .set_locale_override(params)
+        .await
+        .map_err(|e| BrowserError::command("emulation.setLocaleOverride", e))?;
+    Ok(())
+}
+
+/// Overrides the timezone, e.g. `"Asia/Tokyo"` or `"+09:00"`, or removes the override when `None`.
+pub async fn set_timezone(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    timezone: Option<&str>,
+) -> Result<(), BrowserError> {
+    let (contexts, user_contexts) = scope.targets();
+    let params = SetTimezoneOverrideParameters {
+        timezone: timezone.map(str::to_string),
+        contexts,
+        user_contexts,
+    };
+    session
+        .set_timezone_override(params)
+        .await
+        .map_err(|e| BrowserError::command("emulation.setTimezoneOverride", e))?;
+    Ok(())
+}
+
+/// Replaces the preload script shim registered for a feature in the scope.
+///
+/// The shim runs before page scripts on every new document; for a single browsing
+/// context it is also applied to the current document right away.
+/// Passing `None` removes the shim, which takes effect on the next navigation.
+pub async fn set_shim(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    shims: &ShimRegistry,
+    feature: &str,
+    function_declaration: Option<String>,
+) -> Result<(), BrowserError> {
+    let key = format!("{:?}/{}", scope, feature);
+    let previous = shims.lock().ok().and_then(|mut shims| shims.remove(&key));
+    if let Some(script) = previous {
+        debug!("Removing the {} shim: {}", feature, script);
+        session
+            .script_remove_preload_script(RemovePreloadScriptParameters::new(script))
+            .await
+            .map_err(|e| BrowserError::command("script.removePreloadScript", e))?;
+    }
+
+    let Some(function_declaration) = function_declaration else {
+        return Ok(());
+    };
+
+    let (contexts, user_contexts) = scope.targets();
+    let add_params = AddPreloadScriptParameters::new(
+        function_declaration.clone(),
+        None,
+        contexts,
+        user_contexts,
+        None,
+    );
+    let rslt = session
+        .script_add_preload_script(add_params)
+        .await
+        .map_err(|e| BrowserError::command("script.addPreloadScript", e))?;
+    debug!("Added the {} shim: {}", feature, rslt.script);
+    if let Ok(mut shims) = shims.lock() {
+        shims.insert(key, rslt.script);
+    }
+
+    if let EmulationScope::Context(context) = scope {
+        let target = Target::ContextTarget(ContextTarget::new(context.clone(), None));
+        let params =
+            CallFunctionParameters::new(function_declaration, false, target, None, None, None, None, None);
+        session
+            .script_call_function(params)
+            .await
+            .map_err(|e| BrowserError::command("script.callFunction", e))?;
+    }
+    Ok(())
+}
+
+/// Emulates the `prefers-color-scheme` media feature, or removes the emulation when `None`.
+pub async fn set_color_scheme(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    shims: &ShimRegistry,
+    color_scheme: Option<ColorScheme>,
+) -> Result<(), BrowserError> {
+    let feature = "prefers-color-scheme";
+    let shim = color_scheme.map(|scheme| media_feature_shim(feature, scheme.as_str()));
+    set_shim(session, scope, shims, feature, shim).await
+}
+
+/// Emulates the `prefers-reduced-motion` media feature, or removes the emulation when `None`.
+pub async fn set_reduced_motion(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    shims: &ShimRegistry,
+    reduced_motion: Option<ReducedMotion>,
+) -> Result<(), BrowserError> {
+    let feature = "prefers-reduced-motion";
+    let shim = reduced_motion.map(|motion| media_feature_shim(feature, motion.as_str()));
+    set_shim(session, scope, shims, feature, shim).await
+}
+
+/// Emulates a disconnected network, or restores it.
+///
+/// Uses `emulation.setNetworkConditions` when the remote end supports it, and falls back
+/// to a shim making `navigator.onLine`, `fetch`, `XMLHttpRequest` and `WebSocket` fail.
+pub async fn set_offline(
+    session: &mut WebDriverBiDiSession,
+    scope: &EmulationScope,
+    shims: &ShimRegistry,
+    offline: bool,
+) -> Result<(), BrowserError> {
+    let mut params = scope.params();
+    let network_conditions = offline.then(|| json!({ "type": "offline" }));
+    params.insert("networkConditions".into(), json!(network_conditions));
+    match command::send(session, "emulation.setNetworkConditions", Value::Object(params)).await {
+        Ok(_) => Ok(()),
+        Err(e) if is_unknown_command(&e) => {
+            debug!("emulation.setNetworkConditions is not supported, using the offline shim");
+            let shim = offline.then(|| OFFLINE_SHIM.to_string());
+            set_shim(session, scope, shims, "offline", shim).await
+        }
+        Err(e) => Err(BrowserError::command("emulation.setNetworkConditions", e)),
+    }
+}
+
+// --------------------------------------------------
+
+/// A handle applying emulation settings to a browsing context or a user context.
+///
+/// Obtained with `Browser::emulation` for the current page, or `Browser::emulation_for`
+/// for an explicit scope, e.g. `EmulationScope::default_user_context()` for every page.
+///
+/// Settings fail with a `BrowserError::Disconnected` once the browser is closed or its
+/// connection lost.
+pub struct Emulation {
+    browser: Browser,
+    scope: EmulationScope,
+    shims: ShimRegistry,
+}
+
+impl Emulation {
+    /// Creates a handle applying settings to the given scope.
+    pub(crate) fn new(browser: Browser, scope: EmulationScope, shims: ShimRegistry) -> Self {
+        Self { browser, scope, shims }
+    }
+
+    /// Returns the scope the settings apply to.
+    pub fn scope(&self) -> &EmulationScope {
+        &self.scope
+    }
+
+    /// Returns the session to send a setting with, reconnecting the browser if needed.
+    async fn session(&self) -> Result<WebDriverBiDiSession, BrowserError> {
+        let context = match &self.scope {
+            EmulationScope::Context(context) => Some(context.as_str()),
+            EmulationScope::UserContext(_) => None,
+        };
+        ContextScope::new(&self.browser, context, None)
+            .connected_session()
+            .await
+    }
+
+    /// Sets the viewport size in CSS pixels and, optionally, the device pixel ratio.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if the `browsingContext.setViewport` command fails.
+    pub async fn set_viewport(&self, width: u64, height: u64, device_pixel_ratio: Option<f32>) -> Result<(), BrowserError> {
+        set_viewport(&mut self.session().await?, &self.scope, width, height, device_pixel_ratio).await
+    }
+
+    /// Restores the default viewport size and device pixel ratio.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if the `browsingContext.setViewport` command fails.
+    pub async fn reset_viewport(&self) -> Result<(), BrowserError> {
+        reset_viewport(&mut self.session().await?, &self.scope).await
+    }
+
+    /// Overrides the user agent, or restores the browser's own when `None`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if the `emulation.setUserAgentOverride` command fails.
+    pub async fn set_user_agent(&self, user_agent: Option<&str>) -> Result<(), BrowserError> {
+        set_user_agent(&mut self.session().await?, &self.scope, user_agent).await
+    }
+
+    /// Applies the viewport, device pixel ratio, user agent and touch support of a device.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if any of the emulation commands fails.
+    pub async fn emulate_device(&self, device: &Device) -> Result<(), BrowserError> {
+        emulate_device(&mut self.session().await?, &self.scope, device).await
+    }
+
+    /// Overrides the geolocation position reported to the page.
+    /// The page still needs the geolocation permission to read it.
+    ///
+    /// # Arguments
+    /// - `latitude`, `longitude`: The position in decimal degrees.
+    /// - `accuracy`: The accuracy in meters (default: 1).
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if the `emulation.setGeolocationOverride` command fails.
+    pub async fn set_geolocation(&self, latitude: f64, longitude: f64, accuracy: Option<f64>) -> Result<(), BrowserError> {
+        let mut coordinates = GeolocationCoordinates::new(latitude, longitude);
+        if let Some(accuracy) = accuracy {
+            coordinates.accuracy = accuracy;
+        }
+        set_geolocation(&mut self.session().await?, &self.scope, Some(coordinates)).await
+    }
+
+    /// Removes the geolocation override.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if the `emulation.setGeolocationOverride` command fails.
+    pub async fn clear_geolocation(&self) -> Result<(), BrowserError> {
+        set_geolocation(&mut self.session().await?, &self.scope, None).await
+    }
+
+    /// Overrides the locale, e.g. `Some("de-DE")`, or removes the override when `None`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if the `emulation.setLocaleOverride` command fails.
+    pub async fn set_locale(&self, locale: Option<&str>) -> Result<(), BrowserError> {
+        set_locale(&mut self.session().await?, &self.scope, locale).await
+    }
+
+    /// Overrides the timezone, e.g. `Some("Asia/Tokyo")`, or removes the override when `None`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if the `emulation.setTimezoneOverride` command fails.
+    pub async fn set_timezone(&self, timezone: Option<&str>) -> Result<(), BrowserError> {
+        set_timezone(&mut self.session().await?, &self.scope, timezone).await
+    }
+
+    /// Emulates `prefers-color-scheme` for `window.matchMedia`, or removes the emulation when `None`.
+    /// The shim applies to new documents and, for a single browsing context, the current one;
+    /// CSS media queries are not affected.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if a `script.addPreloadScript`,
+    /// `script.removePreloadScript` or `script.callFunction` command installing the shim fails.
+    pub async fn set_color_scheme(&self, color_scheme: Option<ColorScheme>) -> Result<(), BrowserError> {
+        set_color_scheme(&mut self.session().await?, &self.scope, &self.shims, color_scheme).await
+    }
+
+    /// Emulates `prefers-reduced-motion` for `window.matchMedia`, or removes the emulation when `None`.
+    /// The shim applies to new documents and, for a single browsing context, the current one;
+    /// CSS media queries are not affected.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if a `script.addPreloadScript`,
+    /// `script.removePreloadScript` or `script.callFunction` command installing the shim fails.
+    pub async fn set_reduced_motion(&self, reduced_motion: Option<ReducedMotion>) -> Result<(), BrowserError> {
+        set_reduced_motion(&mut self.session().await?, &self.scope, &self.shims, reduced_motion).await
+    }
+
+    /// Emulates a disconnected network, or restores it.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Protocol` if `emulation.setNetworkConditions` fails for
+    /// another reason than being unsupported, or if installing the fallback shim fails.
+    pub async fn set_offline(&self, offline: bool) -> Result<(), BrowserError> {
+        set_offline(&mut self.session().await?, &self.scope, &self.shims, offline).await
+    }
+}
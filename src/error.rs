@@ -1,13 +1,100 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::Value;
 use thiserror::Error;
+use tokio_tungstenite::tungstenite;
+use webdriverbidi::error::CommandError;
+
+// --------------------------------------------------
+
+/// How long webdriverbidi waits for the response to a command.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
 
+// --------------------------------------------------
+
+/// The errors returned by autom8.
+///
+/// Failures of WebDriver BiDi commands are reported by cause, whatever the operation:
+/// `Protocol` when the remote end answered with an error, `Transport` when the command
+/// couldn't be sent or its response read, `Timeout` when no response arrived in time, and
+/// `Disconnected` when the connection to the driver was lost before the response. All but
+/// `Disconnected` keep the `webdriverbidi` error as their `source`. Scripts throwing are
+/// reported as `ScriptException` and selectors matching nothing as `ElementNotFound`. The other
+/// variants describe the failures detected by autom8 itself, per operation. When an
+/// `ArtifactPolicy` is set, the errors of failed actions come wrapped in `WithArtifacts`;
+/// `inner` and `into_inner` return the wrapped error.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::BrowserError;
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// match browser.click_element("#submit").await.map_err(|e| e.into_inner()) {
+///     Ok(()) => {}
+///     Err(BrowserError::ElementNotFound { locator }) => println!("{} is missing", locator),
+///     Err(BrowserError::Protocol { code, .. }) if code == "no such frame" => println!("The page is gone"),
+///     Err(e) => return Err(e.into()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, Error)]
 pub enum BrowserError {
+    #[error("No element matches {locator}")]
+    ElementNotFound { locator: String },
+
+    #[error("{operation} timed out after {} milliseconds", .elapsed.as_millis())]
+    Timeout {
+        operation: String,
+        elapsed: Duration,
+        #[source]
+        source: Option<Box<CommandError>>,
+    },
+
+    #[error("Script exception: {message}")]
+    ScriptException {
+        message: String,
+        /// The JavaScript stack trace, one `at function (url:line:column)` frame per line.
+        stack: Option<String>,
+    },
+
+    #[error("The {command} command failed with {code}: {message}")]
+    Protocol {
+        command: String,
+        /// The WebDriver BiDi error code, e.g. `no such frame` or `invalid argument`.
+        code: String,
+        message: String,
+        #[source]
+        source: Box<CommandError>,
+    },
+
+    #[error("The {command} command couldn't be completed: {source}")]
+    Transport {
+        command: String,
+        #[source]
+        source: Box<CommandError>,
+    },
+
+    #[error("{source} (failure artifacts saved to {})", .artifacts.display())]
+    WithArtifacts {
+        #[source]
+        source: Box<BrowserError>,
+        /// The directory the failure artifacts were saved to, see `ArtifactPolicy`.
+        artifacts: PathBuf,
+    },
+
     #[error("Session creation error: {0}")]
     SessionCreation(String),
 
     #[error("Session closing error: {0}")]
     SessionClosing(String),
 
+    #[error("Launch error: {0}")]
+    Launch(String),
+
+    #[error("Disconnected: {0}")]
+    Disconnected(String),
+
     #[error("Navigation error: {0}")]
     Navigation(String),
 
@@ -17,21 +104,105 @@ pub enum BrowserError {
     #[error("Element error: {0}")]
     Element(String),
 
-    #[error("Cookie error: {0}")]
-    Cookie(String),
+    #[error("Frame error: {0}")]
+    Frame(String),
+
+    #[error("User context error: {0}")]
+    UserContext(String),
 
     #[error("JavaScript error: {0}")]
     JavaScript(String),
 
-    #[error("LocalStorage error: {0}")]
-    LocalStorage(String),
-
     #[error("Screenshot error: {0}")]
     Screenshot(String),
 
+    #[error("Print error: {0}")]
+    Print(String),
+
+    #[error("Recording error: {0}")]
+    Recording(String),
+
     #[error("Assertion error: {0}")]
     Assertion(String),
+}
+
+impl BrowserError {
+    /// Reports the failure of a WebDriver BiDi command by cause.
+    pub(crate) fn command(command: &str, error: CommandError) -> Self {
+        match &error {
+            CommandError::Error(response) => {
+                let field = |name: &str| response.get(name).and_then(Value::as_str).map(str::to_string);
+                Self::Protocol {
+                    command: command.to_string(),
+                    code: field("error").unwrap_or_else(|| "unknown error".to_string()),
+                    message: field("message").unwrap_or_default(),
+                    source: Box::new(error),
+                }
+            }
+            CommandError::TimeoutError => Self::Timeout {
+                operation: format!("The {} command", command),
+                elapsed: COMMAND_TIMEOUT,
+                source: Some(Box::new(error)),
+            },
+            // The connection was lost while the command awaited its response
+            CommandError::OneshotReceiverError(_) => Self::Disconnected(format!(
+                "The connection to the driver was lost during the {} command",
+                command
+            )),
+            CommandError::WebSocketSendError(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                Self::Disconnected(format!(
+                    "The {} command couldn't be sent, the connection to the driver is closed",
+                    command
+                ))
+            }
+            _ => Self::Transport {
+                command: command.to_string(),
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// Returns the directory the failure artifacts of the error were saved to, if any.
+    pub fn artifacts(&self) -> Option<&Path> {
+        match self {
+            Self::WithArtifacts { artifacts, .. } => Some(artifacts),
+            _ => None,
+        }
+    }
+
+    /// Returns the error the action failed with, without its failure artifacts.
+    pub fn inner(&self) -> &BrowserError {
+        match self {
+            Self::WithArtifacts { source, .. } => source,
+            _ => self,
+        }
+    }
+
+    /// Returns the error the action failed with, dropping its failure artifacts.
+    pub fn into_inner(self) -> BrowserError {
+        match self {
+            Self::WithArtifacts { source, .. } => *source,
+            _ => self,
+        }
+    }
+
+    /// Reports a missing element by the full locator the caller passed, including the
+    /// frame segments resolved before looking the element up.
+    pub(crate) fn for_locator(self, locator: &str) -> Self {
+        match self {
+            Self::ElementNotFound { .. } => Self::ElementNotFound {
+                locator: locator.to_string(),
+            },
+            error => error,
+        }
+    }
 
-    #[error("Unknown error: {0}")]
-    Unknown(String),
+    /// Returns a `Timeout` for an operation autom8 stopped waiting for.
+    pub(crate) fn timeout(operation: impl Into<String>, elapsed: Duration) -> Self {
+        Self::Timeout {
+            operation: operation.into(),
+            elapsed,
+            source: None,
+        }
+    }
 }
@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+use serde_json::Value;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+// --------------------------------------------------
+
+/// Receives the raw WebDriver BiDi events a `Browser` subscribed to, e.g. with
+/// `Browser::subscribe_events`.
+pub type EventReceiver = UnboundedReceiver<Value>;
+
+/// A subscriber called with every event of its method, returning `false` to unsubscribe.
+type Callback = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+
+/// Identifies a subscriber of an `EventDispatcher`, to remove it with `unsubscribe`.
+pub type SubscriberId = u64;
+
+struct Subscriber {
+    id: SubscriberId,
+    method: String,
+    callback: Callback,
+}
+
+// --------------------------------------------------
+
+/// Routes the events received on a connection to the subscribers of their method.
+///
+/// Events are dispatched one after the other from a task owned by the connection, with no
+/// lock held, so subscribers must return quickly: they forward events to a channel or
+/// spawn a task, and never run user code themselves.
+#[derive(Default)]
+pub struct EventDispatcher {
+    subscribers: Mutex<Vec<Subscriber>>,
+    next_id: AtomicU64,
+}
+
+impl EventDispatcher {
+    /// Calls `callback` with every event of `method` until it returns `false` or is
+    /// removed with `unsubscribe`.
+    pub fn subscribe<F>(&self, method: &str, callback: F) -> SubscriberId
+    where
+        F: Fn(&Value) -> bool + Send + Sync + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(Subscriber {
+                id,
+                method: method.to_string(),
+                callback: Arc::new(callback),
+            });
+        }
+        id
+    }
+
+    /// Stops calling a subscriber. The WebDriver BiDi subscription to its events is kept.
+    pub fn unsubscribe(&self, id: SubscriberId) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| subscriber.id != id);
+        }
+    }
+
+    /// Returns a receiver of the events of the given methods.
+    pub fn receiver(&self, methods: &[&str]) -> EventReceiver {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        for method in methods {
+            let sender = sender.clone();
+            self.subscribe(method, move |event| sender.send(event.clone()).is_ok());
+        }
+        receiver
+    }
+
+    /// Calls the subscribers of the event's method, dropping those that unsubscribed.
+    pub fn dispatch(&self, event: &Value) {
+        let Some(method) = event.get("method").and_then(Value::as_str) else {
+            return;
+        };
+        let callbacks: Vec<Callback> = match self.subscribers.lock() {
+            Ok(subscribers) => subscribers
+                .iter()
+                .filter(|subscriber| subscriber.method == method)
+                .map(|subscriber| subscriber.callback.clone())
+                .collect(),
+            Err(_) => return,
+        };
+        if callbacks.is_empty() {
+            debug!("No subscriber for event {}", method);
+        }
+
+        let done: Vec<Callback> = callbacks.into_iter().filter(|callback| !callback(event)).collect();
+        if done.is_empty() {
+            return;
+        }
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| !done.iter().any(|callback| Arc::ptr_eq(callback, &subscriber.callback)));
+        }
+    }
+}
@@ -7,6 +7,7 @@ use webdriverbidi::model::script::{
 // --------------------------------------------------
 
 use crate::error::BrowserError;
+use crate::{frame, script, selector};
 
 // --------------------------------------------------
 
@@ -15,6 +16,7 @@ use crate::error::BrowserError;
 /// # Arguments
 /// - `session`: The WebDriverBiDiSession to use for script execution
 /// - `context`: The browsing context where the element should be found
+/// - `sandbox`: The sandbox realm to run the script in, or `None` for the page realm
 /// - `selector`: CSS selector to identify the element
 /// 
 /// # Returns
@@ -28,18 +30,21 @@ use crate::error::BrowserError;
 pub async fn extract_inner_html(
     session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     selector: &str,
 ) -> Result<String, BrowserError> {
+    let locator = selector;
+    let (context, selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
     debug!("Extracting inner HTML for element with selector: {}", selector);
     
-    // Escape double quotes in the selector to prevent JavaScript syntax errors
-    let escaped_selector = selector.replace("\"", "\\\"");
+    // Build the element lookup expression, which also handles shadow-piercing selectors
+    let element_query = selector::query_selector(&selector);
     
     // JavaScript that finds the element and returns its innerHTML
     let script = format!(
         r#"
         (() => {{
-            const element = document.querySelector("{}");
+            const element = {};
             if (element) {{
                 return element.innerHTML;
             }} else {{
@@ -47,16 +52,16 @@ pub async fn extract_inner_html(
             }}
         }})()
         "#,
-        escaped_selector
+        element_query
     );
     
-    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), None));
+    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), sandbox.map(str::to_string)));
     let params = EvaluateParameters::new(script, target, false, None, None, None);
     
     let result = session
         .script_evaluate(params)
         .await
-        .map_err(|e| BrowserError::Element(format!("Script evaluation failed: {}", e)))?;
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
 
     match result {
         EvaluateResult::EvaluateResultSuccess(success) => {
@@ -70,7 +75,7 @@ pub async fn extract_inner_html(
                 RemoteValue::PrimitiveProtocolValue(
                     PrimitiveProtocolValue::NullValue(_)
                 ) => {
-                    Err(BrowserError::Element(format!("Element not found with selector: {}", selector)))
+                    Err(BrowserError::ElementNotFound { locator: locator.to_string() })
                 }
                 _ => {
                     debug!("Unexpected result type from innerHTML extraction: {:?}", success.result);
@@ -79,7 +84,7 @@ pub async fn extract_inner_html(
             }
         }
         EvaluateResult::EvaluateResultException(exception) => {
-            Err(BrowserError::Element(format!("Script exception during innerHTML extraction: {:?}", exception.exception_details)))
+            Err(script::exception_details_error(&exception.exception_details))
         }
         EvaluateResult::EmptyResult(_) => {
             Err(BrowserError::Element("Empty result from innerHTML extraction script".to_string()))
@@ -93,6 +98,7 @@ pub async fn extract_inner_html(
 /// # Arguments
 /// - `session`: The WebDriverBiDiSession to use for script execution
 /// - `context`: The browsing context where the element should be found
+/// - `sandbox`: The sandbox realm to run the script in, or `None` for the page realm
 /// - `selector`: CSS selector to identify the element
 /// 
 /// # Returns
@@ -101,16 +107,19 @@ pub async fn extract_inner_html(
 pub async fn extract_inner_text(
     session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     selector: &str,
 ) -> Result<String, BrowserError> {
+    let locator = selector;
+    let (context, selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
     debug!("Extracting inner text for element with selector: {}", selector);
     
-    let escaped_selector = selector.replace("\"", "\\\"");
+    let element_query = selector::query_selector(&selector);
     
     let script = format!(
         r#"
         (() => {{
-            const element = document.querySelector("{}");
+            const element = {};
             if (element) {{
                 return element.innerText;
             }} else {{
@@ -118,16 +127,16 @@ pub async fn extract_inner_text(
             }}
         }})()
         "#,
-        escaped_selector
+        element_query
     );
     
-    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), None));
+    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), sandbox.map(str::to_string)));
     let params = EvaluateParameters::new(script, target, false, None, None, None);
     
     let result = session
         .script_evaluate(params)
         .await
-        .map_err(|e| BrowserError::Element(format!("Script evaluation failed: {}", e)))?;
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
 
     match result {
         EvaluateResult::EvaluateResultSuccess(success) => {
@@ -141,7 +150,7 @@ pub async fn extract_inner_text(
                 RemoteValue::PrimitiveProtocolValue(
                     PrimitiveProtocolValue::NullValue(_)
                 ) => {
-                    Err(BrowserError::Element(format!("Element not found with selector: {}", selector)))
+                    Err(BrowserError::ElementNotFound { locator: locator.to_string() })
                 }
                 _ => {
                     debug!("Unexpected result type from innerText extraction: {:?}", success.result);
@@ -150,7 +159,7 @@ pub async fn extract_inner_text(
             }
         }
         EvaluateResult::EvaluateResultException(exception) => {
-            Err(BrowserError::Element(format!("Script exception during innerText extraction: {:?}", exception.exception_details)))
+            Err(script::exception_details_error(&exception.exception_details))
         }
         EvaluateResult::EmptyResult(_) => {
             Err(BrowserError::Element("Empty result from innerText extraction script".to_string()))
@@ -163,6 +172,7 @@ pub async fn extract_inner_text(
 /// # Arguments
 /// - `session`: The WebDriverBiDiSession to use for script execution
 /// - `context`: The browsing context where the element should be found
+/// - `sandbox`: The sandbox realm to run the script in, or `None` for the page realm
 /// - `selector`: CSS selector to identify the element
 /// - `attribute`: The name of the attribute to extract
 /// 
@@ -173,18 +183,21 @@ pub async fn extract_inner_text(
 pub async fn extract_attribute(
     session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     selector: &str,
     attribute: &str,
 ) -> Result<Option<String>, BrowserError> {
+    let locator = selector;
+    let (context, selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
     debug!("Extracting attribute '{}' for element with selector: {}", attribute, selector);
     
-    let escaped_selector = selector.replace("\"", "\\\"");
+    let element_query = selector::query_selector(&selector);
     let escaped_attribute = attribute.replace("\"", "\\\"");
     
     let script = format!(
         r#"
         (() => {{
-            const element = document.querySelector("{}");
+            const element = {};
             if (element) {{
                 return element.getAttribute("{}");
             }} else {{
@@ -192,16 +205,16 @@ pub async fn extract_attribute(
             }}
         }})()
         "#,
-        escaped_selector, escaped_attribute
+        element_query, escaped_attribute
     );
     
-    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), None));
+    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), sandbox.map(str::to_string)));
     let params = EvaluateParameters::new(script, target, false, None, None, None);
     
     let result = session
         .script_evaluate(params)
         .await
-        .map_err(|e| BrowserError::Element(format!("Script evaluation failed: {}", e)))?;
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
 
     match result {
         EvaluateResult::EvaluateResultSuccess(success) => {
@@ -220,7 +233,7 @@ pub async fn extract_attribute(
                 RemoteValue::PrimitiveProtocolValue(
                     PrimitiveProtocolValue::UndefinedValue(_)
                 ) => {
-                    Err(BrowserError::Element(format!("Element not found with selector: {}", selector)))
+                    Err(BrowserError::ElementNotFound { locator: locator.to_string() })
                 }
                 _ => {
                     debug!("Unexpected result type from attribute extraction: {:?}", success.result);
@@ -229,7 +242,7 @@ pub async fn extract_attribute(
             }
         }
         EvaluateResult::EvaluateResultException(exception) => {
-            Err(BrowserError::Element(format!("Script exception during attribute extraction: {:?}", exception.exception_details)))
+            Err(script::exception_details_error(&exception.exception_details))
         }
         EvaluateResult::EmptyResult(_) => {
             Err(BrowserError::Element("Empty result from attribute extraction script".to_string()))
@@ -0,0 +1,28 @@
+use std::fs;
+use std::path::Path;
+
+use log::debug;
+
+// --------------------------------------------------
+
+use crate::error::BrowserError;
+
+// --------------------------------------------------
+
+/// Writes bytes to a file, creating its parent directories. Failures are reported with
+/// the error variant of the caller, e.g. `BrowserError::Screenshot`.
+pub fn write_file(
+    path: &Path,
+    bytes: &[u8],
+    error: fn(String) -> BrowserError,
+) -> Result<(), BrowserError> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| {
+            error(format!("Creating the directory {} failed: {}", parent.display(), e))
+        })?;
+    }
+    fs::write(path, bytes)
+        .map_err(|e| error(format!("Writing {} failed: {}", path.display(), e)))?;
+    debug!("Saved {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,480 @@
+use std::path::Path;
+
+use log::debug;
+use serde_json::{Value, json};
+use webdriverbidi::model::browsing_context::{GetTreeParameters, Info};
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::browser::Browser;
+use crate::element::Element;
+use crate::error::BrowserError;
+use crate::scope::ContextScope;
+use crate::screenshot::ScreenshotOptions;
+use crate::{command, script, selector};
+
+// --------------------------------------------------
+
+/// Separates the frame segments of a frame-piercing locator,
+/// e.g. `iframe#checkout |> button.pay`.
+pub const FRAME_SEPARATOR: &str = "|>";
+
+/// Returns a JavaScript function finding a frame element by name, id or
+/// (shadow-piercing) CSS selector and returning its window.
+fn find_frame_fn() -> String {
+    format!(
+        r#"
+        (locator) => {{
+            const frames = Array.from(document.querySelectorAll("iframe, frame"));
+            let element = frames.find((f) => f.name === locator || f.id === locator);
+            if (!element) {{
+                try {{
+                    element = ({})(locator);
+                }} catch (e) {{
+                    element = null;
+                }}
+            }}
+            return element && element.contentWindow ? element.contentWindow : null;
+        }}
+        "#,
+        selector::QUERY_SELECTOR_FN
+    )
+}
+
+/// Lists the frames of the document with the offset of their content box in the viewport.
+const FRAME_OFFSETS_FN: &str = r#"
+    () => Array.from(document.querySelectorAll("iframe, frame"), (frame) => {
+        const rect = frame.getBoundingClientRect();
+        const style = getComputedStyle(frame);
+        return [
+            frame.contentWindow,
+            rect.left + frame.clientLeft + parseFloat(style.paddingLeft),
+            rect.top + frame.clientTop + parseFloat(style.paddingTop),
+        ];
+    })
+"#;
+
+// --------------------------------------------------
+
+/// Returns the browsing context of the frame identified by a name, id or CSS selector
+/// within the given context. The locator may itself use the frame-piercing syntax.
+pub async fn frame_context(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    locator: &str,
+) -> Result<String, BrowserError> {
+    let mut ctx = context.to_string();
+    for segment in locator.split(FRAME_SEPARATOR).map(str::trim) {
+        ctx = child_frame_context(session, &ctx, sandbox, segment).await?;
+    }
+    Ok(ctx)
+}
+
+/// Splits a frame-piercing locator into the browsing context of its innermost frame
+/// and the remaining element selector. Plain selectors are returned unchanged.
+pub async fn resolve_locator(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    locator: &str,
+) -> Result<(String, String), BrowserError> {
+    match locator.rsplit_once(FRAME_SEPARATOR) {
+        Some((frames, selector)) => {
+            let ctx = frame_context(session, context, sandbox, frames).await?;
+            Ok((ctx, selector.trim().to_string()))
+        }
+        None => Ok((context.to_string(), locator.to_string())),
+    }
+}
+
+/// Looks up a direct child frame of the given context.
+async fn child_frame_context(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    locator: &str,
+) -> Result<String, BrowserError> {
+    debug!("Looking up frame '{}' in context: {}", locator, context);
+    let params = json!({
+        "functionDeclaration": find_frame_fn(),
+        "awaitPromise": false,
+        "target": script::context_target(context, sandbox),
+        "arguments": [{ "type": "string", "value": locator }],
+    });
+    let result = command::send(session, "script.callFunction", params)
+        .await
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
+
+    match result.get("type").and_then(Value::as_str) {
+        Some("success") => result
+            .pointer("/result/value/context")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| BrowserError::Frame(format!("Frame not found: {}", locator))),
+        Some("exception") => Err(script::exception_error(&result)),
+        _ => Err(BrowserError::Frame(
+            "Unexpected result from frame lookup script".to_string(),
+        )),
+    }
+}
+
+/// Returns the parent of a browsing context, or `None` for a top-level one.
+async fn parent_context(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+) -> Result<Option<String>, BrowserError> {
+    let get_tree_params = GetTreeParameters::new(Some(0), Some(context.to_string()));
+    let get_tree_rslt = session
+        .browsing_context_get_tree(get_tree_params)
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.getTree", e))?;
+    Ok(get_tree_rslt.contexts.into_iter().next().and_then(|info| info.parent))
+}
+
+/// Returns the top-level browsing context (tab or window) containing the given context.
+pub async fn top_level_context(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+) -> Result<String, BrowserError> {
+    let mut ctx = context.to_string();
+    while let Some(parent) = parent_context(session, &ctx).await? {
+        ctx = parent;
+    }
+    Ok(ctx)
+}
+
+/// Returns the offset, in CSS pixels, of the viewport of a frame in the viewport of one
+/// of its ancestor contexts.
+pub async fn frame_offset(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    ancestor: &str,
+    sandbox: Option<&str>,
+) -> Result<(f64, f64), BrowserError> {
+    let (mut ctx, mut x, mut y) = (context.to_string(), 0.0, 0.0);
+    while ctx != ancestor {
+        let parent = parent_context(session, &ctx).await?.ok_or_else(|| {
+            BrowserError::Frame(format!("The browsing context {} is not nested in {}", context, ancestor))
+        })?;
+        let (frame_x, frame_y) = child_frame_offset(session, &parent, sandbox, &ctx).await?;
+        x += frame_x;
+        y += frame_y;
+        ctx = parent;
+    }
+    Ok((x, y))
+}
+
+/// Returns the offset of the content box of a direct child frame in the viewport of `context`.
+async fn child_frame_offset(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    child: &str,
+) -> Result<(f64, f64), BrowserError> {
+    let params = json!({
+        "functionDeclaration": FRAME_OFFSETS_FN,
+        "awaitPromise": false,
+        "target": script::context_target(context, sandbox),
+    });
+    let result = command::send(session, "script.callFunction", params)
+        .await
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
+    if result.get("type").and_then(Value::as_str) == Some("exception") {
+        return Err(script::exception_error(&result));
+    }
+
+    let frames = result.pointer("/result/value").and_then(Value::as_array);
+    frames
+        .into_iter()
+        .flatten()
+        .filter_map(|frame| frame.get("value").and_then(Value::as_array))
+        .find(|frame| frame.first().and_then(|window| window.pointer("/value/context")) == Some(&Value::from(child)))
+        .map(|frame| {
+            let number = |index: usize| frame.get(index).and_then(|n| n.get("value")).and_then(Value::as_f64);
+            (number(1).unwrap_or_default(), number(2).unwrap_or_default())
+        })
+        .ok_or_else(|| BrowserError::Frame(format!("Frame element of {} not found in {}", child, context)))
+}
+
+/// Returns the `browsingContext.getTree` entry of the frame identified by a name, id or
+/// CSS selector within the given context.
+pub async fn get_frame(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    locator: &str,
+) -> Result<Info, BrowserError> {
+    let ctx = frame_context(session, context, sandbox, locator).await?;
+    let get_tree_params = GetTreeParameters::new(Some(0), Some(ctx.clone()));
+    let get_tree_rslt = session
+        .browsing_context_get_tree(get_tree_params)
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.getTree", e))?;
+
+    get_tree_rslt
+        .contexts
+        .into_iter()
+        .next()
+        .ok_or_else(|| BrowserError::Frame(format!("Frame not found: {}", locator)))
+}
+
+/// Returns the `browsingContext.getTree` entries of the frames nested in the given context,
+/// up to `max_depth` levels deep (all levels when `None`), in depth-first order.
+pub async fn get_frames(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    max_depth: Option<u64>,
+) -> Result<Vec<Info>, BrowserError> {
+    let get_tree_params = GetTreeParameters::new(max_depth, Some(context.to_string()));
+    let get_tree_rslt = session
+        .browsing_context_get_tree(get_tree_params)
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.getTree", e))?;
+
+    let mut frames = Vec::new();
+    for info in get_tree_rslt.contexts {
+        collect_frames(info.children.unwrap_or_default(), &mut frames);
+    }
+    Ok(frames)
+}
+
+fn collect_frames(infos: Vec<Info>, frames: &mut Vec<Info>) {
+    for mut info in infos {
+        let children = info.children.take().unwrap_or_default();
+        frames.push(info);
+        collect_frames(children, frames);
+    }
+}
+
+// --------------------------------------------------
+
+/// A handle to a child browsing context (an `iframe` or `frame`) of a page.
+///
+/// Frames share the connection of the `Browser` they were obtained from, and expose the
+/// same context-scoped operations, run against the frame's document in the sandbox the
+/// `Browser` was configured with. Like pages, they fail with a `BrowserError::Disconnected`
+/// once the browser is closed or its connection lost.
+pub struct Frame {
+    browser: Browser,
+    context: String,
+    parent: Option<String>,
+    url: String,
+    sandbox: Option<String>,
+}
+
+impl Frame {
+    /// Creates a handle to the frame described by a `browsingContext.getTree` entry.
+    pub(crate) fn from_info(browser: Browser, sandbox: Option<String>, info: Info) -> Self {
+        Self {
+            browser,
+            context: info.context,
+            parent: info.parent,
+            url: info.url,
+            sandbox,
+        }
+    }
+
+    fn scope(&self) -> ContextScope<'_> {
+        ContextScope::for_frame(&self.browser, &self.context, self.sandbox.clone())
+    }
+
+    /// Returns the browsing context identifier of the frame.
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Returns the browsing context identifier of the parent frame or page.
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+
+    /// Returns the URL of the frame at the time it was retrieved.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns all frames nested in this frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.getTree` command fails.
+    pub async fn frames(&self) -> Result<Vec<Frame>, BrowserError> {
+        self.scope().frames().await
+    }
+
+    /// Returns the child frame identified by a name, id or CSS selector.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Frame` if no matching frame is found.
+    pub async fn frame(&self, locator: &str) -> Result<Frame, BrowserError> {
+        self.scope().frame(locator).await
+    }
+}
+
+// Navigation
+impl Frame {
+    /// Navigates the frame to the specified URL.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.navigate` command fails.
+    pub async fn load(&self, url: &str) -> Result<(), BrowserError> {
+        self.scope().load(url).await
+    }
+
+    /// Reloads the frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if reloading fails.
+    pub async fn reload(&self) -> Result<(), BrowserError> {
+        self.scope().reload().await
+    }
+
+    /// Waits for the frame's document to finish loading.
+    ///
+    /// # Arguments
+    /// - `timeout_ms`: Maximum time to wait for page load in milliseconds (default: 10000)
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Timeout` if the document doesn't load within the timeout.
+    pub async fn wait_for_page_load(&self, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().wait_for_page_load(timeout_ms).await
+    }
+}
+
+// JavaScript evaluation
+impl Frame {
+    /// Evaluates a JavaScript expression in the frame's page realm, awaiting it if it's
+    /// a promise.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the expression throws.
+    pub async fn evaluate(&self, expression: &str) -> Result<Value, BrowserError> {
+        self.scope().evaluate(expression, None).await
+    }
+
+    /// Calls a JavaScript function in the frame's page realm, awaiting it if it returns
+    /// a promise.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the function throws.
+    pub async fn call_function(&self, function_declaration: &str, arguments: &[Value]) -> Result<Value, BrowserError> {
+        self.scope().call_function(function_declaration, arguments).await
+    }
+}
+
+// Screenshots
+impl Frame {
+    /// Takes a screenshot of the frame and returns the decoded image bytes.
+    ///
+    /// The frame is captured from its top-level page: without a clip, the visible part of
+    /// the frame is captured, and clip boxes are relative to the frame's viewport.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command
+    /// fails, or a `BrowserError::Screenshot` if decoding the screenshot fails.
+    pub async fn screenshot_bytes(&self, options: &ScreenshotOptions) -> Result<Vec<u8>, BrowserError> {
+        self.scope().screenshot_bytes(options).await
+    }
+
+    /// Takes a screenshot of the frame and saves it to a file, creating its parent directories.
+    /// The image format is inferred from the file extension (`png`, `jpg`, `jpeg` or `webp`).
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command
+    /// fails, or a `BrowserError::Screenshot` if the extension is not supported or if the
+    /// file can't be written.
+    pub async fn save_screenshot(&self, path: impl AsRef<Path>) -> Result<(), BrowserError> {
+        self.scope().save_screenshot(path.as_ref(), &ScreenshotOptions::default()).await
+    }
+}
+
+// Local storage
+impl Frame {
+    /// Sets a value in the local storage of the frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.callFunction` command fails.
+    pub async fn set_local_storage_value(&self, key: &str, value: &str) -> Result<(), BrowserError> {
+        self.scope().set_local_storage_value(key, value).await
+    }
+
+    /// Gets a value from the local storage of the frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.callFunction` command fails.
+    pub async fn get_local_storage_value(&self, key: &str) -> Result<Option<String>, BrowserError> {
+        self.scope().get_local_storage_value(key).await
+    }
+}
+
+// Elements and assertions
+impl Frame {
+    /// Returns a handle to the first element of the frame matching the selector.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn element(&self, selector: &str) -> Result<Element, BrowserError> {
+        self.scope().element(selector).await
+    }
+
+    /// Asserts that an element matching the selector is present in the frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.evaluate` command fails, or a
+    /// `BrowserError::ScriptException` if the selector is invalid.
+    pub async fn assert_element_present(&self, selector: &str) -> Result<bool, BrowserError> {
+        self.scope().assert_element_present(selector).await
+    }
+}
+
+// Input/Interaction and extraction
+impl Frame {
+    /// Clicks on an element of the frame identified by a CSS selector.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn click_element(&self, selector: &str) -> Result<(), BrowserError> {
+        self.scope().click_element(selector).await
+    }
+
+    /// Clicks on an element of the frame after waiting for it to become clickable.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Timeout` if the element doesn't become clickable within the timeout.
+    pub async fn wait_and_click_element(&self, selector: &str, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().wait_and_click_element(selector, timeout_ms).await
+    }
+
+    /// Clicks an element of the frame and then waits for the frame to finish loading.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError` if clicking fails or the frame doesn't load within timeout.
+    pub async fn click_and_wait(&self, selector: &str, page_load_timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().click_and_wait(selector, page_load_timeout_ms).await
+    }
+
+    /// Extracts the inner HTML of an element of the frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn extract_inner_html(&self, selector: &str) -> Result<String, BrowserError> {
+        self.scope().extract_inner_html(selector).await
+    }
+
+    /// Extracts the inner text of an element of the frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn extract_inner_text(&self, selector: &str) -> Result<String, BrowserError> {
+        self.scope().extract_inner_text(selector).await
+    }
+
+    /// Extracts the value of an attribute from an element of the frame.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn extract_attribute(&self, selector: &str, attribute: &str) -> Result<Option<String>, BrowserError> {
+        self.scope().extract_attribute(selector, attribute).await
+    }
+}
@@ -0,0 +1,246 @@
+use log::{debug, error};
+use serde_json::{Value, json};
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::command;
+use crate::error::BrowserError;
+use crate::remote_value;
+use crate::script;
+
+// --------------------------------------------------
+
+const GET_PROPERTY_FN: &str = "(object, name) => object[name]";
+const CALL_METHOD_FN: &str = "(object, name, ...args) => object[name](...args)";
+const IDENTITY_FN: &str = "(object) => object";
+
+// --------------------------------------------------
+
+/// Evaluates an expression, keeping the resulting object alive until the handle is disposed.
+pub async fn evaluate_handle(
+    session: &mut WebDriverBiDiSession,
+    target: Value,
+    expression: &str,
+) -> Result<JsHandle, BrowserError> {
+    debug!("Evaluating handle in {}", target);
+    let params = json!({
+        "expression": expression,
+        "target": target,
+        "awaitPromise": true,
+        "resultOwnership": "root",
+    });
+    let result = command::send(session, "script.evaluate", params)
+        .await
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
+    JsHandle::from_evaluate_result(session, &result)
+}
+
+/// Calls a function in a realm, passing serialized `script.LocalValue` arguments,
+/// and returns the result as a handle.
+async fn call_function_handle(
+    session: &mut WebDriverBiDiSession,
+    realm: &str,
+    function_declaration: &str,
+    arguments: Vec<Value>,
+) -> Result<JsHandle, BrowserError> {
+    let params = json!({
+        "functionDeclaration": function_declaration,
+        "arguments": arguments,
+        "target": script::realm_target(realm),
+        "awaitPromise": true,
+        "resultOwnership": "root",
+    });
+    let result = command::send(session, "script.callFunction", params)
+        .await
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
+    JsHandle::from_evaluate_result(session, &result)
+}
+
+/// Releases a handle so that its object can be garbage collected.
+async fn disown(
+    session: &mut WebDriverBiDiSession,
+    realm: &str,
+    handle: &str,
+) -> Result<(), BrowserError> {
+    let params = json!({
+        "handles": [handle],
+        "target": script::realm_target(realm),
+    });
+    command::send(session, "script.disown", params)
+        .await
+        .map_err(|e| BrowserError::command("script.disown", e))?;
+    debug!("Handle disowned: {}", handle);
+    Ok(())
+}
+
+// --------------------------------------------------
+
+/// A handle to a live JavaScript value of a realm.
+///
+/// Objects are kept alive by the browser until the handle is disposed, either
+/// explicitly with `dispose` or when the handle is dropped. Primitive values
+/// have no remote handle and are held by value.
+pub struct JsHandle {
+    session: WebDriverBiDiSession,
+    realm: String,
+    remote_value: Value,
+    disposed: bool,
+}
+
+impl JsHandle {
+    /// Builds a handle from a raw `script.EvaluateResult`.
+    fn from_evaluate_result(
+        session: &WebDriverBiDiSession,
+        result: &Value,
+    ) -> Result<Self, BrowserError> {
+        match result.get("type").and_then(Value::as_str) {
+            Some("success") => Ok(Self {
+                session: session.clone(),
+                realm: result
+                    .get("realm")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                remote_value: result.get("result").cloned().unwrap_or(Value::Null),
+                disposed: false,
+            }),
+            Some("exception") => Err(script::exception_error(result)),
+            _ => Err(BrowserError::JavaScript(
+                "Unexpected result from script evaluation".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the realm the value lives in.
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    /// Returns the type of the value, e.g. `"object"`, `"array"`, `"node"` or `"string"`.
+    pub fn value_type(&self) -> &str {
+        self.remote_value
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Returns the remote handle of the value, `None` for primitives.
+    pub fn handle(&self) -> Option<&str> {
+        self.remote_value.get("handle").and_then(Value::as_str)
+    }
+
+    /// Returns the shared id of a DOM node, usable across realms of the same document.
+    pub fn shared_id(&self) -> Option<&str> {
+        self.remote_value.get("sharedId").and_then(Value::as_str)
+    }
+
+    /// Returns the `script.LocalValue` referencing this value in commands.
+    fn local_value(&self) -> Value {
+        match (self.handle(), self.shared_id()) {
+            (Some(handle), _) => json!({ "handle": handle }),
+            (None, Some(shared_id)) => json!({ "sharedId": shared_id }),
+            (None, None) => match self.value_type() {
+                "undefined" | "null" | "string" | "number" | "boolean" | "bigint" => {
+                    self.remote_value.clone()
+                }
+                _ => json!({ "type": "undefined" }),
+            },
+        }
+    }
+
+    /// Returns a handle to a property of the value.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the property access throws.
+    pub async fn get_property(&mut self, name: &str) -> Result<JsHandle, BrowserError> {
+        let arguments = vec![self.local_value(), remote_value::from_json(&Value::from(name))];
+        call_function_handle(&mut self.session, &self.realm, GET_PROPERTY_FN, arguments).await
+    }
+
+    /// Calls a method of the value with JSON arguments, awaiting it if it returns a promise,
+    /// and returns a handle to the result.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the method throws.
+    pub async fn call_method(&mut self, name: &str, arguments: &[Value]) -> Result<JsHandle, BrowserError> {
+        let mut call_arguments = vec![self.local_value(), remote_value::from_json(&Value::from(name))];
+        call_arguments.extend(arguments.iter().map(remote_value::from_json));
+        call_function_handle(&mut self.session, &self.realm, CALL_METHOD_FN, call_arguments).await
+    }
+
+    /// Returns the current value as JSON, serialized from the live object.
+    ///
+    /// Values without a JSON representation, such as functions or DOM nodes, become `null`.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::JavaScript` if serialization fails.
+    pub async fn json_value(&mut self) -> Result<Value, BrowserError> {
+        if self.handle().is_none() {
+            return Ok(remote_value::to_json(&self.remote_value));
+        }
+        let target = script::realm_target(&self.realm);
+        let params = json!({
+            "functionDeclaration": IDENTITY_FN,
+            "arguments": [self.local_value()],
+            "target": target,
+            "awaitPromise": false,
+        });
+        let result = command::send(&mut self.session, "script.callFunction", params)
+            .await
+            .map_err(|e| BrowserError::command("script.callFunction", e))?;
+        match result.get("type").and_then(Value::as_str) {
+            Some("success") => Ok(result
+                .get("result")
+                .map(remote_value::to_json)
+                .unwrap_or(Value::Null)),
+            Some("exception") => Err(script::exception_error(&result)),
+            _ => Err(BrowserError::JavaScript(
+                "Unexpected result from serializing the value".to_string(),
+            )),
+        }
+    }
+
+    /// Releases the value so that it can be garbage collected.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.disown` command fails.
+    pub async fn dispose(mut self) -> Result<(), BrowserError> {
+        self.disposed = true;
+        match self.handle().map(str::to_string) {
+            Some(handle) => disown(&mut self.session, &self.realm, &handle).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for JsHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsHandle")
+            .field("realm", &self.realm)
+            .field("remote_value", &self.remote_value)
+            .finish()
+    }
+}
+
+impl Drop for JsHandle {
+    fn drop(&mut self) {
+        if self.disposed {
+            return;
+        }
+        let Some(handle) = self.handle().map(str::to_string) else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            error!("No runtime to disown handle {} on drop", handle);
+            return;
+        };
+        let mut session = self.session.clone();
+        let realm = self.realm.clone();
+        runtime.spawn(async move {
+            if let Err(e) = disown(&mut session, &realm, &handle).await {
+                error!("{}", e);
+            }
+        });
+    }
+}
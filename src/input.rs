@@ -7,6 +7,7 @@ use webdriverbidi::model::script::{
 // --------------------------------------------------
 
 use crate::error::BrowserError;
+use crate::{frame, script, selector};
 
 // --------------------------------------------------
 
@@ -15,6 +16,7 @@ use crate::error::BrowserError;
 /// # Arguments
 /// - `session`: The WebDriverBiDiSession to use for script execution
 /// - `context`: The browsing context where the element should be found
+/// - `sandbox`: The sandbox realm to run the script in, or `None` for the page realm
 /// - `selector`: CSS selector to identify the element to click
 /// 
 /// # Returns
@@ -29,18 +31,21 @@ use crate::error::BrowserError;
 pub async fn click_element(
     session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     selector: &str,
 ) -> Result<(), BrowserError> {
+    let locator = selector;
+    let (context, selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
     debug!("Attempting to click element with selector: {}", selector);
     
-    // Escape double quotes in the selector to prevent JavaScript syntax errors
-    let escaped_selector = selector.replace("\"", "\\\"");
+    // Build the element lookup expression, which also handles shadow-piercing selectors
+    let element_query = selector::query_selector(&selector);
     
     // JavaScript that finds the element, checks if it exists, and clicks it
     let script = format!(
         r#"
         (() => {{
-            const element = document.querySelector("{}");
+            const element = {};
             if (element) {{
                 // Scroll element into view if needed
                 element.scrollIntoView({{ behavior: 'auto', block: 'center' }});
@@ -54,16 +59,16 @@ pub async fn click_element(
             }}
         }})()
         "#,
-        escaped_selector
+        element_query
     );
     
-    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), None));
+    let target = Target::ContextTarget(ContextTarget::new(context.to_string(), sandbox.map(str::to_string)));
     let params = EvaluateParameters::new(script, target, false, None, None, None); // awaitPromise = false
     
     let result = session
         .script_evaluate(params)
         .await
-        .map_err(|e| BrowserError::Action(format!("Script evaluation failed: {}", e)))?;
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
 
     match result {
         EvaluateResult::EvaluateResultSuccess(success) => {
@@ -75,7 +80,7 @@ pub async fn click_element(
                         debug!("Successfully clicked element with selector: {}", selector);
                         Ok(())
                     } else {
-                        Err(BrowserError::Action(format!("Element not found with selector: {}", selector)))
+                        Err(BrowserError::ElementNotFound { locator: locator.to_string() })
                     }
                 }
                 _ => {
@@ -85,7 +90,7 @@ pub async fn click_element(
             }
         }
         EvaluateResult::EvaluateResultException(exception) => {
-            Err(BrowserError::Action(format!("Script exception during click: {:?}", exception.exception_details)))
+            Err(script::exception_details_error(&exception.exception_details))
         }
         EvaluateResult::EmptyResult(_) => {
             Err(BrowserError::Action("Empty result from click script evaluation".to_string()))
@@ -99,31 +104,36 @@ pub async fn click_element(
 /// # Arguments
 /// - `session`: The WebDriverBiDiSession to use for script execution
 /// - `context`: The browsing context where the element should be found
+/// - `sandbox`: The sandbox realm to run the script in, or `None` for the page realm
 /// - `selector`: CSS selector to identify the element to click
 /// - `timeout_ms`: Maximum time to wait for element to be clickable (default: 5000ms)
 /// 
 /// # Returns
 /// - `Ok(())` if the element was found, became clickable, and was clicked successfully
 /// - `Err(BrowserError)` if the element was not found or didn't become clickable within timeout
+#[allow(clippy::collapsible_if)]
 pub async fn wait_and_click_element(
     session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     selector: &str,
     timeout_ms: Option<u64>,
 ) -> Result<(), BrowserError> {
+    let locator = selector;
+    let (context, selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
     let timeout = std::time::Duration::from_millis(timeout_ms.unwrap_or(5000));
     let start_time = std::time::Instant::now();
     
     debug!("Waiting for element to be clickable with selector: {}", selector);
     
-    let escaped_selector = selector.replace("\"", "\\\"");
+    let element_query = selector::query_selector(&selector);
     
     while start_time.elapsed() < timeout {
         // Check if element exists and is clickable
         let check_script = format!(
             r#"
             (() => {{
-                const element = document.querySelector("{}");
+                const element = {};
                 if (element) {{
                     const rect = element.getBoundingClientRect();
                     const style = window.getComputedStyle(element);
@@ -139,10 +149,10 @@ pub async fn wait_and_click_element(
                 return false;
             }})()
             "#,
-            escaped_selector
+            element_query
         );
         
-        let target = Target::ContextTarget(ContextTarget::new(context.to_string(), None));
+        let target = Target::ContextTarget(ContextTarget::new(context.to_string(), sandbox.map(str::to_string)));
         let params = EvaluateParameters::new(check_script, target, false, None, None, None);
         
         match session.script_evaluate(params).await {
@@ -152,7 +162,9 @@ pub async fn wait_and_click_element(
                 ) = success.result {
                     if bool_val.value {
                         debug!("Element is now clickable, proceeding with click");
-                        return click_element(session, context, selector).await;
+                        return click_element(session, &context, sandbox, &selector)
+                            .await
+                            .map_err(|e| e.for_locator(locator));
                     }
                 }
             }
@@ -168,9 +180,8 @@ pub async fn wait_and_click_element(
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
     }
     
-    Err(BrowserError::Action(format!(
-        "Element with selector '{}' did not become clickable within {} milliseconds",
-        selector,
-        timeout.as_millis()
-    )))
+    Err(BrowserError::timeout(
+        format!("Waiting for the element '{}' to become clickable", locator),
+        start_time.elapsed(),
+    ))
 }
@@ -0,0 +1,385 @@
+use std::env;
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use log::{debug, error};
+use tokio::net::TcpStream;
+use webdriverbidi::webdriver::capabilities::CapabilitiesRequest;
+
+// --------------------------------------------------
+
+use crate::browser::Browser;
+use crate::error::BrowserError;
+use crate::options::BrowserOptions;
+
+// --------------------------------------------------
+
+const HOST: &str = "127.0.0.1";
+
+static NEXT_PROFILE_ID: AtomicU64 = AtomicU64::new(0);
+
+// --------------------------------------------------
+
+/// A browser that can be launched through its WebDriver driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowserKind {
+    /// Firefox, driven by geckodriver.
+    Firefox,
+    /// Chrome or Chromium, driven by chromedriver.
+    Chrome,
+}
+
+impl BrowserKind {
+    /// Returns the `browserName` capability of the browser.
+    pub fn browser_name(&self) -> &'static str {
+        match self {
+            Self::Firefox => "firefox",
+            Self::Chrome => "chrome",
+        }
+    }
+
+    /// Returns the file name of the driver binary, without extension.
+    pub fn driver_name(&self) -> &'static str {
+        match self {
+            Self::Firefox => "geckodriver",
+            Self::Chrome => "chromedriver",
+        }
+    }
+
+    /// Returns the environment variable holding the path of the driver binary.
+    pub fn driver_env(&self) -> &'static str {
+        match self {
+            Self::Firefox => "GECKODRIVER",
+            Self::Chrome => "CHROMEDRIVER",
+        }
+    }
+
+    /// Returns the environment variable holding the path of the browser binary.
+    pub fn browser_env(&self) -> &'static str {
+        match self {
+            Self::Firefox => "FIREFOX_BINARY",
+            Self::Chrome => "CHROME_BINARY",
+        }
+    }
+
+    /// Returns the vendor capability holding the browser options.
+    pub(crate) fn options_capability(&self) -> &'static str {
+        match self {
+            Self::Firefox => "moz:firefoxOptions",
+            Self::Chrome => "goog:chromeOptions",
+        }
+    }
+
+    fn port_args(&self, port: u16) -> Vec<String> {
+        match self {
+            Self::Firefox => vec!["--port".to_string(), port.to_string()],
+            Self::Chrome => vec![format!("--port={}", port)],
+        }
+    }
+}
+
+// --------------------------------------------------
+
+/// Launches a local driver (geckodriver or chromedriver) and opens a `Browser` on it.
+///
+/// The driver binary is taken from, in order: the explicit path, the `GECKODRIVER` or
+/// `CHROMEDRIVER` environment variable, and the `PATH`. The browser binary is optional and
+/// taken from the explicit path or the `FIREFOX_BINARY` or `CHROME_BINARY` environment
+/// variable, otherwise the driver looks for the browser itself.
+///
+/// The driver runs in a process group of its own, with the browser it starts. The whole
+/// group is killed, and the temporary profile directory removed, when the `Browser` is
+/// closed or dropped.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::Launcher;
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let browser = Launcher::firefox().headless(true).launch().await?;
+/// browser.load("https://example.com").await?;
+/// browser.close().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Launcher {
+    options: BrowserOptions,
+    driver_path: Option<PathBuf>,
+    profile_dir: Option<PathBuf>,
+    startup_timeout: Duration,
+}
+
+impl Launcher {
+    /// Creates a launcher for a headless browser with a temporary profile.
+    pub fn new(kind: BrowserKind) -> Self {
+        Self::from_options(BrowserOptions::new(kind))
+    }
+
+    /// Creates a launcher for a browser configured by `options`, with a temporary profile.
+    pub fn from_options(options: BrowserOptions) -> Self {
+        Self {
+            options,
+            driver_path: None,
+            profile_dir: None,
+            startup_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Creates a launcher for Firefox, driven by geckodriver.
+    pub fn firefox() -> Self {
+        Self::new(BrowserKind::Firefox)
+    }
+
+    /// Creates a launcher for Chrome, driven by chromedriver.
+    pub fn chrome() -> Self {
+        Self::new(BrowserKind::Chrome)
+    }
+
+    /// Sets the path of the driver binary.
+    pub fn driver_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.driver_path = Some(path.into());
+        self
+    }
+
+    /// Sets the path of the browser binary.
+    pub fn browser_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options = self.options.binary(path);
+        self
+    }
+
+    /// Sets whether the browser runs without a visible window. Defaults to `true`.
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.options = self.options.headless(headless);
+        self
+    }
+
+    /// Uses an existing profile directory, kept after the browser is closed,
+    /// instead of a temporary one.
+    pub fn profile_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.profile_dir = Some(dir.into());
+        self
+    }
+
+    /// Adds a command line argument passed to the browser.
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.options = self.options.arg(arg);
+        self
+    }
+
+    /// Sets how long to wait for the driver to accept connections. Defaults to 30 seconds.
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Returns the kind of browser launched.
+    pub fn kind(&self) -> BrowserKind {
+        self.options.kind()
+    }
+
+    /// Spawns the driver on a free port, waits for it to accept connections
+    /// and opens a browser session on it.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Launch` if the driver can't be found or started, a
+    /// `BrowserError::Timeout` if it doesn't accept connections within the startup timeout,
+    /// or a `BrowserError::SessionCreation` if the session can't be opened.
+    #[allow(clippy::collapsible_if)]
+    pub async fn launch(self) -> Result<Browser, BrowserError> {
+        let driver = self.find_driver()?;
+        let port = free_port()?;
+        let mut process = DriverProcess::default();
+
+        let profile_dir = match &self.profile_dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = temp_profile_dir()?;
+                process.temp_dirs.push(dir.clone());
+                dir
+            }
+        };
+
+        debug!("Launching {} on port {}", driver.display(), port);
+        let mut command = Command::new(&driver);
+        command
+            .args(self.kind().port_args(port))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // Starts a new process group, so that the browser can be killed with the driver
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let child = command
+            .spawn()
+            .map_err(|e| {
+                BrowserError::Launch(format!("Starting {} failed: {}", driver.display(), e))
+            })?;
+        process.child = Some(child);
+        process.wait_until_ready(port, self.startup_timeout).await?;
+
+        let mut options = self.options;
+        if !options.has_binary() {
+            if let Some(path) = env::var_os(options.kind().browser_env()) {
+                options = options.binary(path);
+            }
+        }
+        let capabilities = options.capability_request(Some(&profile_dir));
+
+        let browser =
+            Browser::new_with_capabilities(CapabilitiesRequest::new(capabilities), HOST, port);
+        browser.attach_process(process);
+        browser.open().await?;
+        Ok(browser)
+    }
+
+    /// Locates the driver binary from the explicit path, the environment or the `PATH`.
+    fn find_driver(&self) -> Result<PathBuf, BrowserError> {
+        let kind = self.kind();
+        if let Some(path) = &self.driver_path {
+            return Ok(path.clone());
+        }
+        if let Some(path) = env::var_os(kind.driver_env()) {
+            return Ok(PathBuf::from(path));
+        }
+        let file_name = format!("{}{}", kind.driver_name(), env::consts::EXE_SUFFIX);
+        env::var_os("PATH")
+            .iter()
+            .flat_map(env::split_paths)
+            .map(|dir| dir.join(&file_name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                BrowserError::Launch(format!(
+                    "{} not found, set {} or add it to the PATH",
+                    kind.driver_name(),
+                    kind.driver_env()
+                ))
+            })
+    }
+}
+
+// --------------------------------------------------
+
+/// Returns a port that is free at the time of the call.
+fn free_port() -> Result<u16, BrowserError> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .map_err(|e| BrowserError::Launch(format!("Finding a free port failed: {}", e)))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| BrowserError::Launch(format!("Finding a free port failed: {}", e)))?
+        .port();
+    Ok(port)
+}
+
+/// Creates an empty temporary profile directory.
+fn temp_profile_dir() -> Result<PathBuf, BrowserError> {
+    let dir = env::temp_dir().join(format!(
+        "autom8-profile-{}-{}",
+        std::process::id(),
+        NEXT_PROFILE_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).map_err(|e| {
+        BrowserError::Launch(format!(
+            "Creating the profile directory {} failed: {}",
+            dir.display(),
+            e
+        ))
+    })?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+
+/// A driver process launched by autom8, killed and cleaned up on shutdown or drop.
+#[derive(Default)]
+pub(crate) struct DriverProcess {
+    child: Option<Child>,
+    temp_dirs: Vec<PathBuf>,
+}
+
+impl DriverProcess {
+    /// Waits until the driver accepts connections on the port.
+    #[allow(clippy::collapsible_if)]
+    async fn wait_until_ready(&mut self, port: u16, timeout: Duration) -> Result<(), BrowserError> {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let start_time = Instant::now();
+        while start_time.elapsed() < timeout {
+            if let Some(child) = self.child.as_mut() {
+                if let Ok(Some(status)) = child.try_wait() {
+                    return Err(BrowserError::Launch(format!(
+                        "The driver exited during startup with {}",
+                        status
+                    )));
+                }
+            }
+            let connect = TcpStream::connect(address);
+            if let Ok(Ok(_)) = tokio::time::timeout(Duration::from_millis(100), connect).await {
+                debug!("Driver ready on port {}", port);
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(BrowserError::timeout(
+            format!("Waiting for the driver to listen on port {}", port),
+            start_time.elapsed(),
+        ))
+    }
+
+    /// Kills the driver and the browser it started, then removes the temporary directories.
+    ///
+    /// Killing the driver alone would leave the browser running, so on Unix its whole
+    /// process group is killed, and on Windows its process tree.
+    pub(crate) fn shutdown(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            debug!("Killing the driver process {} and its children", child.id());
+            if let Err(e) = kill_tree(&mut child) {
+                debug!("Killing the driver process failed: {}", e);
+            }
+            let _ = child.wait();
+        }
+        for dir in self.temp_dirs.drain(..) {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                error!("Removing {} failed: {}", dir.display(), e);
+            }
+        }
+    }
+}
+
+impl Drop for DriverProcess {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Kills the process group led by the driver, which includes the browser.
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) -> std::io::Result<()> {
+    let Ok(pgid) = libc::pid_t::try_from(child.id()) else {
+        return child.kill();
+    };
+    // SAFETY: `kill` has no memory safety requirements; a negative pid targets the group
+    if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        child.kill()
+    }
+}
+
+/// Kills the driver and the processes it started, the browser included.
+#[cfg(windows)]
+fn kill_tree(child: &mut Child) -> std::io::Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() { Ok(()) } else { child.kill() }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn kill_tree(child: &mut Child) -> std::io::Result<()> {
+    child.kill()
+}
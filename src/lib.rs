@@ -1,10 +1,60 @@
+mod apng;
+mod artifacts;
 mod assertions;
+mod binding;
 pub mod browser;
+mod channel;
+mod command;
+mod connect;
+mod device;
+mod dialog;
+mod element;
+mod emulation;
 mod error;
+mod events;
 mod extract;
+mod files;
+mod frame;
+mod handle;
+mod launcher;
 mod local_storage;
 mod nav;
+mod options;
+mod page;
+mod pool;
+mod preload;
+mod print;
+mod realm;
+mod recording;
+mod remote_value;
+mod scope;
+mod script;
 mod screenshot;
+mod selector;
+mod user_context;
 mod input;
+mod visual;
 
+pub use artifacts::ArtifactPolicy;
 pub use browser::Browser;
+pub use channel::{ChannelMessage, ChannelReceiver};
+pub use connect::ReconnectPolicy;
+pub use device::Device;
+pub use dialog::{Dialog, DialogAction, DialogCallback, DialogPolicy, DialogType};
+pub use element::Element;
+pub use emulation::{ColorScheme, DEFAULT_USER_CONTEXT, Emulation, EmulationScope, ReducedMotion};
+pub use error::BrowserError;
+pub use events::EventReceiver;
+pub use frame::Frame;
+pub use handle::JsHandle;
+pub use launcher::{BrowserKind, Launcher};
+pub use options::{BrowserOptions, PageLoadStrategy, PromptBehavior, Proxy};
+pub use page::Page;
+pub use pool::{BrowserPool, PooledPage};
+pub use preload::PreloadScript;
+pub use print::{Orientation, PrintOptions};
+pub use realm::{RealmEvent, RealmEventReceiver, RealmFilter, RealmInfo, RealmType};
+pub use recording::{RecordingOptions, RecordingOutput};
+pub use screenshot::{ImageFormat, ScreenshotOptions, ScreenshotOrigin};
+pub use script::DEFAULT_SANDBOX;
+pub use visual::{ScreenshotMatchOptions, UPDATE_BASELINES_ENV};
@@ -20,14 +20,15 @@ fn local_value(str: &str) -> LocalValue {
     )))
 }
 
-fn target_context(context: &str) -> Target {
-    Target::ContextTarget(ContextTarget::new(context.to_string(), None))
+fn target_context(context: &str, sandbox: Option<&str>) -> Target {
+    Target::ContextTarget(ContextTarget::new(context.to_string(), sandbox.map(str::to_string)))
 }
 
 /// Sets the value for the key in the context's localStorage.
 pub async fn set_local_storage(
     bidi_session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     key: &str,
     value: &str,
 ) -> Result<(), BrowserError> {
@@ -38,7 +39,7 @@ pub async fn set_local_storage(
     let params = CallFunctionParameters::new(
         function_declaration,
         false,
-        target_context(context),
+        target_context(context, sandbox),
         args,
         None,
         None,
@@ -48,9 +49,7 @@ pub async fn set_local_storage(
     bidi_session
         .script_call_function(params)
         .await
-        .map_err(|e| {
-            BrowserError::LocalStorage(format!("Setting the local storage value failed: {}", e))
-        })?;
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
 
     Ok(())
 }
@@ -59,6 +58,7 @@ pub async fn set_local_storage(
 pub async fn get_local_storage(
     bidi_session: &mut WebDriverBiDiSession,
     context: &str,
+    sandbox: Option<&str>,
     key: &str,
 ) -> Result<Option<String>, BrowserError> {
     let function_declaration = "(key) => localStorage.getItem(key)".to_string();
@@ -68,7 +68,7 @@ pub async fn get_local_storage(
     let params = CallFunctionParameters::new(
         function_declaration,
         false,
-        target_context(context),
+        target_context(context, sandbox),
         args,
         None,
         None,
@@ -78,9 +78,7 @@ pub async fn get_local_storage(
     let eval_result = bidi_session
         .script_call_function(params)
         .await
-        .map_err(|e| {
-            BrowserError::LocalStorage(format!("Getting the local storage value failed: {}", e))
-        })?;
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
 
     match eval_result {
         EvaluateResult::EvaluateResultSuccess(eval_rslt_success) => {
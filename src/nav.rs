@@ -27,7 +27,7 @@ async fn traverse_history(
     session
         .browsing_context_traverse_history(traverse_history_params)
         .await
-        .map_err(|e| BrowserError::Navigation(format!("Navigating the history failed: {}", e)))?;
+        .map_err(|e| BrowserError::command("browsingContext.traverseHistory", e))?;
     Ok(())
 }
 
@@ -47,7 +47,7 @@ pub async fn load(
     session
         .browsing_context_navigate(navigate_params)
         .await
-        .map_err(|e| BrowserError::Navigation(e.to_string()))?;
+        .map_err(|e| BrowserError::command("browsingContext.navigate", e))?;
     Ok(())
 }
 
@@ -84,7 +84,7 @@ pub async fn reload(
     session
         .browsing_context_reload(reload_params)
         .await
-        .map_err(|e| BrowserError::Navigation(e.to_string()))?;
+        .map_err(|e| BrowserError::command("browsingContext.reload", e))?;
     Ok(())
 }
 
@@ -97,13 +97,16 @@ pub async fn reload(
 /// # Arguments
 /// - `session`: The WebDriverBiDiSession to use
 /// - `context`: The browsing context to check
+/// - `sandbox`: The sandbox realm to run the script in, or `None` for the page realm
 /// - `timeout_ms`: Maximum time to wait for page load in milliseconds (default: 10000)
 ///
 /// # Errors
-/// Returns a `BrowserError::NavigationError` if the page doesn't load within the timeout
+/// Returns a `BrowserError::Timeout` if the page doesn't load within the timeout.
+#[allow(clippy::collapsible_if)]
 pub async fn wait_for_page_load(
     session: &mut WebDriverBiDiSession,
     context: String,
+    sandbox: Option<&str>,
     timeout_ms: Option<u64>,
 ) -> Result<(), BrowserError> {
     let timeout = Duration::from_millis(timeout_ms.unwrap_or(10000));
@@ -114,7 +117,7 @@ pub async fn wait_for_page_load(
     while start_time.elapsed() < timeout {
         // Check document ready state
         let script = "document.readyState";
-        let target = Target::ContextTarget(ContextTarget::new(context.clone(), None));
+        let target = Target::ContextTarget(ContextTarget::new(context.clone(), sandbox.map(str::to_string)));
         let params = EvaluateParameters::new(script.to_string(), target, false, None, None, None);
         
         match session.script_evaluate(params).await {
@@ -155,8 +158,5 @@ pub async fn wait_for_page_load(
         tokio::time::sleep(Duration::from_millis(200)).await;
     }
     
-    Err(BrowserError::Navigation(format!(
-        "Page load timeout after {} milliseconds", 
-        timeout.as_millis()
-    )))
+    Err(BrowserError::timeout("Waiting for the page to load", start_time.elapsed()))
 }
@@ -0,0 +1,451 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value, json};
+use webdriverbidi::webdriver::capabilities::{CapabilitiesRequest, CapabilityRequest};
+
+// --------------------------------------------------
+
+use crate::browser::Browser;
+use crate::error::BrowserError;
+use crate::launcher::{BrowserKind, Launcher};
+
+// --------------------------------------------------
+
+/// When navigation commands consider a page loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageLoadStrategy {
+    /// Waits for the `load` event.
+    Normal,
+    /// Waits for the `DOMContentLoaded` event.
+    Eager,
+    /// Returns as soon as the navigation starts.
+    None,
+}
+
+impl PageLoadStrategy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Eager => "eager",
+            Self::None => "none",
+        }
+    }
+}
+
+/// What the browser does with a dialog that no handler closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptBehavior {
+    /// Accepts the dialog.
+    Accept,
+    /// Dismisses the dialog.
+    Dismiss,
+    /// Accepts the dialog and fails the next command.
+    AcceptAndNotify,
+    /// Dismisses the dialog and fails the next command.
+    DismissAndNotify,
+    /// Leaves the dialog open.
+    Ignore,
+}
+
+impl PromptBehavior {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Accept => "accept",
+            Self::Dismiss => "dismiss",
+            Self::AcceptAndNotify => "accept and notify",
+            Self::DismissAndNotify => "dismiss and notify",
+            Self::Ignore => "ignore",
+        }
+    }
+}
+
+/// The proxy the browser connects through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proxy {
+    /// Connects directly, ignoring the system proxy.
+    Direct,
+    /// Uses the proxy configured in the operating system.
+    System,
+    /// Detects the proxy with WPAD.
+    AutoDetect,
+    /// Uses the proxy auto-config file at the URL.
+    Pac(String),
+    /// Uses the given `host:port` proxies, bypassing them for the `no_proxy` hosts.
+    Manual {
+        http: Option<String>,
+        https: Option<String>,
+        socks: Option<String>,
+        no_proxy: Vec<String>,
+    },
+}
+
+impl Proxy {
+    /// Creates a manual proxy used for both HTTP and HTTPS, e.g. `"localhost:8080"`.
+    pub fn manual(host_port: &str) -> Self {
+        Self::Manual {
+            http: Some(host_port.to_string()),
+            https: Some(host_port.to_string()),
+            socks: None,
+            no_proxy: Vec::new(),
+        }
+    }
+
+    /// Creates a SOCKS 5 proxy, e.g. `"localhost:1080"`.
+    pub fn socks(host_port: &str) -> Self {
+        Self::Manual {
+            http: None,
+            https: None,
+            socks: Some(host_port.to_string()),
+            no_proxy: Vec::new(),
+        }
+    }
+
+    fn to_capability(&self) -> Value {
+        match self {
+            Self::Direct => json!({ "proxyType": "direct" }),
+            Self::System => json!({ "proxyType": "system" }),
+            Self::AutoDetect => json!({ "proxyType": "autodetect" }),
+            Self::Pac(url) => json!({ "proxyType": "pac", "proxyAutoconfigUrl": url }),
+            Self::Manual {
+                http,
+                https,
+                socks,
+                no_proxy,
+            } => {
+                let mut proxy = Map::new();
+                proxy.insert("proxyType".to_string(), Value::from("manual"));
+                if let Some(http) = http {
+                    proxy.insert("httpProxy".to_string(), Value::from(http.as_str()));
+                }
+                if let Some(https) = https {
+                    proxy.insert("sslProxy".to_string(), Value::from(https.as_str()));
+                }
+                if let Some(socks) = socks {
+                    proxy.insert("socksProxy".to_string(), Value::from(socks.as_str()));
+                    proxy.insert("socksVersion".to_string(), Value::from(5));
+                }
+                if !no_proxy.is_empty() {
+                    proxy.insert("noProxy".to_string(), Value::from(no_proxy.clone()));
+                }
+                Value::Object(proxy)
+            }
+        }
+    }
+}
+
+// --------------------------------------------------
+
+/// Typed options for a browser session, compiled into WebDriver capabilities.
+///
+/// The same options connect to a driver that is already running, with
+/// `Browser::new_with_options`, or launch a local one, with `BrowserOptions::launch`.
+/// Browsers start headless unless `headless(false)` is set.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::{BrowserOptions, PageLoadStrategy, Proxy};
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let browser = BrowserOptions::firefox()
+///     .window_size(1280, 800)
+///     .accept_insecure_certs(true)
+///     .proxy(Proxy::manual("localhost:8080"))
+///     .downloads_dir("target/downloads")
+///     .page_load_strategy(PageLoadStrategy::Eager)
+///     .pref("intl.accept_languages", "fr-FR")
+///     .launch()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserOptions {
+    kind: BrowserKind,
+    headless: bool,
+    window_size: Option<(u32, u32)>,
+    accept_insecure_certs: Option<bool>,
+    proxy: Option<Proxy>,
+    user_agent: Option<String>,
+    downloads_dir: Option<PathBuf>,
+    page_load_strategy: Option<PageLoadStrategy>,
+    unhandled_prompt_behavior: Option<PromptBehavior>,
+    binary: Option<PathBuf>,
+    args: Vec<String>,
+    prefs: Map<String, Value>,
+    extensions: Map<String, Value>,
+}
+
+impl BrowserOptions {
+    /// Creates headless options for a browser, leaving everything else to the driver defaults.
+    pub fn new(kind: BrowserKind) -> Self {
+        Self {
+            kind,
+            headless: true,
+            window_size: None,
+            accept_insecure_certs: None,
+            proxy: None,
+            user_agent: None,
+            downloads_dir: None,
+            page_load_strategy: None,
+            unhandled_prompt_behavior: None,
+            binary: None,
+            args: Vec::new(),
+            prefs: Map::new(),
+            extensions: Map::new(),
+        }
+    }
+
+    /// Creates options for Firefox.
+    pub fn firefox() -> Self {
+        Self::new(BrowserKind::Firefox)
+    }
+
+    /// Creates options for Chrome.
+    pub fn chrome() -> Self {
+        Self::new(BrowserKind::Chrome)
+    }
+
+    /// Returns the kind of browser the options are for.
+    pub fn kind(&self) -> BrowserKind {
+        self.kind
+    }
+
+    /// Sets whether the browser runs without a visible window. Defaults to `true`.
+    pub fn headless(mut self, headless: bool) -> Self {
+        self.headless = headless;
+        self
+    }
+
+    /// Sets the size of the browser window in pixels.
+    pub fn window_size(mut self, width: u32, height: u32) -> Self {
+        self.window_size = Some((width, height));
+        self
+    }
+
+    /// Sets whether invalid and self-signed TLS certificates are accepted.
+    pub fn accept_insecure_certs(mut self, accept: bool) -> Self {
+        self.accept_insecure_certs = Some(accept);
+        self
+    }
+
+    /// Sets the proxy the browser connects through.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Overrides the user agent sent by the browser.
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Saves downloads to a directory without asking.
+    pub fn downloads_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.downloads_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets when navigation commands consider a page loaded.
+    pub fn page_load_strategy(mut self, strategy: PageLoadStrategy) -> Self {
+        self.page_load_strategy = Some(strategy);
+        self
+    }
+
+    /// Sets what the browser does with a dialog that no handler closes.
+    pub fn unhandled_prompt_behavior(mut self, behavior: PromptBehavior) -> Self {
+        self.unhandled_prompt_behavior = Some(behavior);
+        self
+    }
+
+    /// Sets the path of the browser binary.
+    pub fn binary(mut self, path: impl Into<PathBuf>) -> Self {
+        self.binary = Some(path.into());
+        self
+    }
+
+    /// Adds a command line argument passed to the browser.
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    /// Sets a browser preference: an `about:config` preference for Firefox, or a
+    /// `Preferences` entry such as `"download.prompt_for_download"` for Chrome.
+    pub fn pref(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.prefs.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Adds a raw capability, e.g. a vendor capability not covered by the typed options.
+    /// The `args` and `prefs` of the browser's options capability are merged with the
+    /// typed ones, the typed preferences winning over raw ones of the same name.
+    pub fn capability(mut self, name: &str, value: Value) -> Self {
+        self.extensions.insert(name.to_string(), value);
+        self
+    }
+
+    /// Compiles the options into the capabilities of a new session.
+    pub fn to_capabilities(&self) -> CapabilitiesRequest {
+        CapabilitiesRequest::new(self.capability_request(None))
+    }
+
+    /// Launches a local driver and browser with these options.
+    ///
+    /// # Errors
+    /// See `Launcher::launch`.
+    pub async fn launch(self) -> Result<Browser, BrowserError> {
+        Launcher::from_options(self).launch().await
+    }
+
+    pub(crate) fn has_binary(&self) -> bool {
+        self.binary.is_some()
+    }
+
+    /// Builds the capabilities, adding the profile argument when a profile directory is given.
+    pub(crate) fn capability_request(&self, profile_dir: Option<&Path>) -> CapabilityRequest {
+        let mut capabilities = CapabilityRequest::new();
+        capabilities.browser_name = Some(self.kind.browser_name().to_string());
+        capabilities.accept_insecure_certs = self.accept_insecure_certs;
+        capabilities.proxy = self.proxy.as_ref().map(Proxy::to_capability);
+        capabilities.page_load_strategy = self
+            .page_load_strategy
+            .map(|strategy| strategy.as_str().to_string());
+        capabilities.unhandled_prompt_behavior = self
+            .unhandled_prompt_behavior
+            .map(|behavior| behavior.as_str().to_string());
+        for (name, value) in &self.extensions {
+            capabilities.extension.insert(name.clone(), value.clone());
+        }
+
+        let key = self.kind.options_capability();
+        let mut browser_options = match capabilities.extension.remove(key) {
+            Some(Value::Object(browser_options)) => browser_options,
+            _ => Map::new(),
+        };
+        let mut args = self.browser_args(profile_dir);
+        if let Some(Value::Array(existing)) = browser_options.get("args") {
+            args.extend(existing.iter().filter_map(Value::as_str).map(str::to_string));
+        }
+        if !args.is_empty() {
+            browser_options.insert("args".to_string(), Value::from(args));
+        }
+        let mut prefs = match browser_options.remove("prefs") {
+            Some(Value::Object(existing)) => existing,
+            _ => Map::new(),
+        };
+        prefs.extend(self.browser_prefs());
+        if !prefs.is_empty() {
+            browser_options.insert("prefs".to_string(), Value::Object(prefs));
+        }
+        if let Some(binary) = &self.binary {
+            let binary = Value::from(binary.display().to_string());
+            browser_options.insert("binary".to_string(), binary);
+        }
+        if !browser_options.is_empty() {
+            let browser_options = Value::Object(browser_options);
+            capabilities.extension.insert(key.to_string(), browser_options);
+        }
+        capabilities
+    }
+
+    fn browser_args(&self, profile_dir: Option<&Path>) -> Vec<String> {
+        let mut args = Vec::new();
+        match self.kind {
+            BrowserKind::Firefox => {
+                if self.headless {
+                    args.push("-headless".to_string());
+                }
+                if let Some((width, height)) = self.window_size {
+                    args.extend(["-width".to_string(), width.to_string()]);
+                    args.extend(["-height".to_string(), height.to_string()]);
+                }
+                if let Some(dir) = profile_dir {
+                    args.extend(["-profile".to_string(), dir.display().to_string()]);
+                }
+            }
+            BrowserKind::Chrome => {
+                if self.headless {
+                    args.push("--headless=new".to_string());
+                }
+                if let Some((width, height)) = self.window_size {
+                    args.push(format!("--window-size={},{}", width, height));
+                }
+                if let Some(user_agent) = &self.user_agent {
+                    args.push(format!("--user-agent={}", user_agent));
+                }
+                if let Some(dir) = profile_dir {
+                    args.push(format!("--user-data-dir={}", dir.display()));
+                }
+            }
+        }
+        args.extend(self.args.iter().cloned());
+        args
+    }
+
+    fn browser_prefs(&self) -> Map<String, Value> {
+        let mut prefs = Map::new();
+        match self.kind {
+            BrowserKind::Firefox => {
+                if let Some(user_agent) = &self.user_agent {
+                    prefs.insert(
+                        "general.useragent.override".to_string(),
+                        Value::from(user_agent.as_str()),
+                    );
+                }
+                if let Some(dir) = &self.downloads_dir {
+                    let dir = absolute(dir);
+                    prefs.insert("browser.download.dir".to_string(), Value::from(dir));
+                    prefs.insert("browser.download.folderList".to_string(), Value::from(2));
+                    prefs.insert("browser.download.useDownloadDir".to_string(), Value::from(true));
+                }
+            }
+            BrowserKind::Chrome => {
+                if let Some(dir) = &self.downloads_dir {
+                    let dir = absolute(dir);
+                    prefs.insert("download.default_directory".to_string(), Value::from(dir));
+                    prefs.insert("download.prompt_for_download".to_string(), Value::from(false));
+                }
+            }
+        }
+        for (name, value) in &self.prefs {
+            prefs.insert(name.clone(), value.clone());
+        }
+        prefs
+    }
+}
+
+/// Browsers resolve download directories on their own, so relative paths are made absolute.
+fn absolute(path: &Path) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn raw_prefs_are_merged_with_the_typed_ones() {
+        let options = BrowserOptions::firefox()
+            .capability("moz:firefoxOptions", json!({ "prefs": { "a": 1, "b": 1 } }))
+            .pref("b", 2)
+            .pref("c", 3);
+        let capabilities = options.capability_request(None);
+        assert_eq!(
+            capabilities.extension["moz:firefoxOptions"]["prefs"],
+            json!({ "a": 1, "b": 2, "c": 3 })
+        );
+    }
+
+    #[test]
+    fn raw_prefs_are_kept_without_typed_ones() {
+        let options = BrowserOptions::chrome()
+            .capability("goog:chromeOptions", json!({ "prefs": { "a": 1 } }));
+        let capabilities = options.capability_request(None);
+        assert_eq!(capabilities.extension["goog:chromeOptions"]["prefs"], json!({ "a": 1 }));
+    }
+}
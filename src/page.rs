@@ -0,0 +1,279 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+// --------------------------------------------------
+
+use crate::browser::Browser;
+use crate::element::Element;
+use crate::emulation::{Emulation, EmulationScope};
+use crate::error::BrowserError;
+use crate::frame::Frame;
+use crate::scope::ContextScope;
+use crate::screenshot::ScreenshotOptions;
+use crate::user_context;
+
+// --------------------------------------------------
+
+/// A handle to a top-level browsing context (a tab) of a `Browser`.
+///
+/// Pages share the WebDriver BiDi session of the `Browser` they were created from, and
+/// belong to a user context: pages of different user contexts share no cookies, storage
+/// or cache, so tests running in separate user contexts are isolated from each other.
+/// Like `Browser`, a page can be cloned and driven from several tasks at once, and fails
+/// with a `BrowserError::Disconnected` once the browser is closed or its connection lost.
+///
+/// # Example
+/// ```rust,no_run
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let user_context = browser.new_user_context().await?;
+/// let page = browser.new_page(Some(&user_context)).await?;
+/// page.load("https://example.com/login").await?;
+/// page.click_element("button[type=submit]").await?;
+/// page.close().await?;
+/// browser.remove_user_context(&user_context).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Page {
+    browser: Browser,
+    context: String,
+    user_context: String,
+    sandbox: Option<String>,
+}
+
+impl Page {
+    /// Creates a handle to the page with the given browsing context.
+    pub(crate) fn new(browser: Browser, context: String, user_context: String, sandbox: Option<String>) -> Self {
+        Self {
+            browser,
+            context,
+            user_context,
+            sandbox,
+        }
+    }
+
+    fn scope(&self) -> ContextScope<'_> {
+        ContextScope::new(&self.browser, Some(&self.context), self.sandbox.clone())
+    }
+
+    /// Returns the browsing context identifier of the page.
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Returns the user context the page belongs to, `"default"` unless it was created
+    /// in another one.
+    pub fn user_context(&self) -> &str {
+        &self.user_context
+    }
+
+    /// Closes the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.close` command fails.
+    pub async fn close(self) -> Result<(), BrowserError> {
+        let ctx = self.scope().context().await?;
+        user_context::close_page(&mut self.browser.webdriverbidi_session(), &ctx).await
+    }
+
+    /// Returns an emulation handle applying settings to this page.
+    pub fn emulation(&self) -> Emulation {
+        self.browser.emulation_for(EmulationScope::Context(self.context.clone()))
+    }
+
+    /// Returns all frames of the page, including nested ones.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.getTree` command fails.
+    pub async fn frames(&self) -> Result<Vec<Frame>, BrowserError> {
+        self.scope().frames().await
+    }
+
+    /// Returns the frame identified by a name, id or CSS selector.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Frame` if no matching frame is found.
+    pub async fn frame(&self, locator: &str) -> Result<Frame, BrowserError> {
+        self.scope().frame(locator).await
+    }
+}
+
+// Navigation
+impl Page {
+    /// Navigates the page to the specified URL.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.navigate` command fails.
+    pub async fn load(&self, url: &str) -> Result<(), BrowserError> {
+        self.scope().load(url).await
+    }
+
+    /// Navigates to the previous page in history.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if navigating back failed.
+    pub async fn go_back(&self) -> Result<(), BrowserError> {
+        self.scope().go_back().await
+    }
+
+    /// Navigates to the next page in history.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if navigating forward failed.
+    pub async fn go_forward(&self) -> Result<(), BrowserError> {
+        self.scope().go_forward().await
+    }
+
+    /// Reloads the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Navigation` if reloading fails.
+    pub async fn reload(&self) -> Result<(), BrowserError> {
+        self.scope().reload().await
+    }
+
+    /// Waits for the page to finish loading.
+    ///
+    /// # Arguments
+    /// - `timeout_ms`: Maximum time to wait for page load in milliseconds (default: 10000)
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Timeout` if the page doesn't load within the timeout.
+    pub async fn wait_for_page_load(&self, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().wait_for_page_load(timeout_ms).await
+    }
+}
+
+// JavaScript evaluation
+impl Page {
+    /// Evaluates a JavaScript expression in the page realm, awaiting it if it's a promise.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the expression throws.
+    pub async fn evaluate(&self, expression: &str) -> Result<Value, BrowserError> {
+        self.scope().evaluate(expression, None).await
+    }
+
+    /// Calls a JavaScript function in the page realm, awaiting it if it returns a promise.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ScriptException` if the function throws.
+    pub async fn call_function(&self, function_declaration: &str, arguments: &[Value]) -> Result<Value, BrowserError> {
+        self.scope().call_function(function_declaration, arguments).await
+    }
+}
+
+// Screenshots
+impl Page {
+    /// Takes a screenshot of the page and returns the decoded image bytes.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command
+    /// fails, or a `BrowserError::Screenshot` if decoding the screenshot fails.
+    pub async fn screenshot_bytes(&self, options: &ScreenshotOptions) -> Result<Vec<u8>, BrowserError> {
+        self.scope().screenshot_bytes(options).await
+    }
+
+    /// Takes a screenshot of the page and saves it to a file, creating its parent directories.
+    /// The image format is inferred from the file extension (`png`, `jpg`, `jpeg` or `webp`).
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browsingContext.captureScreenshot` command
+    /// fails, or a `BrowserError::Screenshot` if the extension is not supported or if the
+    /// file can't be written.
+    pub async fn save_screenshot(&self, path: impl AsRef<Path>) -> Result<(), BrowserError> {
+        self.scope().save_screenshot(path.as_ref(), &ScreenshotOptions::default()).await
+    }
+}
+
+// Local storage
+impl Page {
+    /// Sets a value in the local storage of the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.callFunction` command fails.
+    pub async fn set_local_storage_value(&self, key: &str, value: &str) -> Result<(), BrowserError> {
+        self.scope().set_local_storage_value(key, value).await
+    }
+
+    /// Gets a value from the local storage of the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.callFunction` command fails.
+    pub async fn get_local_storage_value(&self, key: &str) -> Result<Option<String>, BrowserError> {
+        self.scope().get_local_storage_value(key).await
+    }
+}
+
+// Elements and assertions
+impl Page {
+    /// Returns a handle to the first element of the page matching the selector.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn element(&self, selector: &str) -> Result<Element, BrowserError> {
+        self.scope().element(selector).await
+    }
+
+    /// Asserts that an element matching the selector is present in the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.evaluate` command fails, or a
+    /// `BrowserError::ScriptException` if the selector is invalid.
+    pub async fn assert_element_present(&self, selector: &str) -> Result<bool, BrowserError> {
+        self.scope().assert_element_present(selector).await
+    }
+}
+
+// Input/Interaction and extraction
+impl Page {
+    /// Clicks on an element of the page identified by a CSS selector.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn click_element(&self, selector: &str) -> Result<(), BrowserError> {
+        self.scope().click_element(selector).await
+    }
+
+    /// Clicks on an element of the page after waiting for it to become clickable.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Timeout` if the element doesn't become clickable within the timeout.
+    pub async fn wait_and_click_element(&self, selector: &str, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().wait_and_click_element(selector, timeout_ms).await
+    }
+
+    /// Clicks an element of the page and then waits for the page to finish loading.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError` if clicking fails or the page doesn't load within timeout.
+    pub async fn click_and_wait(&self, selector: &str, page_load_timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.scope().click_and_wait(selector, page_load_timeout_ms).await
+    }
+
+    /// Extracts the inner HTML of an element of the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn extract_inner_html(&self, selector: &str) -> Result<String, BrowserError> {
+        self.scope().extract_inner_html(selector).await
+    }
+
+    /// Extracts the inner text of an element of the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn extract_inner_text(&self, selector: &str) -> Result<String, BrowserError> {
+        self.scope().extract_inner_text(selector).await
+    }
+
+    /// Extracts the value of an attribute from an element of the page.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::ElementNotFound` if no element matches the selector.
+    pub async fn extract_attribute(&self, selector: &str, attribute: &str) -> Result<Option<String>, BrowserError> {
+        self.scope().extract_attribute(selector, attribute).await
+    }
+}
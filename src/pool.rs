@@ -0,0 +1,284 @@
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use log::{debug, error};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::browser::Browser;
+use crate::error::BrowserError;
+use crate::launcher::Launcher;
+use crate::options::BrowserOptions;
+use crate::page::Page;
+use crate::user_context;
+
+// --------------------------------------------------
+
+struct PoolInner {
+    browsers: Vec<Browser>,
+    // The number of pages leased from each browser
+    leased: Mutex<Vec<usize>>,
+    semaphore: Arc<Semaphore>,
+}
+
+impl PoolInner {
+    /// Counts a lease on the browser with the fewest active leases and returns its index.
+    fn lease(&self) -> usize {
+        let mut leased = self.leased.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = least_leased(&leased);
+        leased[index] += 1;
+        index
+    }
+
+    /// Counts a lease of the browser at `index` as returned.
+    fn release(&self, index: usize) {
+        let mut leased = self.leased.lock().unwrap_or_else(PoisonError::into_inner);
+        leased[index] = leased[index].saturating_sub(1);
+    }
+}
+
+/// Returns the index of the browser with the fewest leases, the first one on ties.
+fn least_leased(leased: &[usize]) -> usize {
+    leased
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, count)| **count)
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+/// Hands out isolated pages from a fixed set of browsers to tests running in parallel.
+///
+/// Every leased page is opened in a user context of its own, so leases share no cookies,
+/// storage or cache. Returning a lease removes its user context, which closes its tabs
+/// and discards its state. At most `max_concurrency` pages are leased at once, spread
+/// over the browsers with the fewest active leases. The pool can be cloned and shared
+/// between tasks.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::{BrowserOptions, BrowserPool};
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// // 2 Firefox sessions serving up to 8 tests at a time
+/// let pool = BrowserPool::launch(&BrowserOptions::firefox(), 2, 8).await?;
+///
+/// let page = pool.acquire().await?;
+/// page.load("https://example.com").await?;
+/// page.release().await?;
+///
+/// pool.close().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BrowserPool {
+    inner: Arc<PoolInner>,
+}
+
+impl BrowserPool {
+    /// Creates a pool from opened browsers, leasing at most `max_concurrency` pages at once.
+    ///
+    /// # Panics
+    /// Panics if `browsers` is empty.
+    pub fn new(browsers: Vec<Browser>, max_concurrency: usize) -> Self {
+        assert!(!browsers.is_empty(), "A browser pool needs at least one browser");
+        Self {
+            inner: Arc::new(PoolInner {
+                leased: Mutex::new(vec![0; browsers.len()]),
+                browsers,
+                semaphore: Arc::new(Semaphore::new(max_concurrency.max(1))),
+            }),
+        }
+    }
+
+    /// Launches `sessions` local browsers with the same options and pools them.
+    ///
+    /// # Errors
+    /// Returns the error of the first browser that fails to launch. The browsers already
+    /// launched are closed when dropped.
+    pub async fn launch(
+        options: &BrowserOptions,
+        sessions: usize,
+        max_concurrency: usize,
+    ) -> Result<Self, BrowserError> {
+        let mut browsers = Vec::with_capacity(sessions.max(1));
+        for _ in 0..sessions.max(1) {
+            browsers.push(Launcher::from_options(options.clone()).launch().await?);
+        }
+        Ok(Self::new(browsers, max_concurrency))
+    }
+
+    /// Waits for a free lease and opens a page in a new user context.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browser.createUserContext` or
+    /// `browsingContext.create` command fails, or a `BrowserError::UserContext` if the pool
+    /// is closed.
+    pub async fn acquire(&self) -> Result<PooledPage, BrowserError> {
+        let permit = self
+            .inner
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| BrowserError::UserContext(format!("The browser pool is closed: {}", e)))?;
+
+        let index = self.inner.lease();
+        let lease = async {
+            let browser = &self.inner.browsers[index];
+            let user_context = browser.new_user_context().await?;
+            match browser.new_page(Some(&user_context)).await {
+                Ok(page) => Ok((page, browser.webdriverbidi_session())),
+                Err(e) => {
+                    let _ = browser.remove_user_context(&user_context).await;
+                    Err(e)
+                }
+            }
+        }
+        .await;
+
+        match lease {
+            Ok((page, session)) => {
+                debug!("Leased page {} from browser {}", page.context(), index);
+                Ok(PooledPage {
+                    page: Some(page),
+                    session,
+                    pool: self.inner.clone(),
+                    index,
+                    permit: Some(permit),
+                })
+            }
+            Err(e) => {
+                self.inner.release(index);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the number of pages that can be leased without waiting.
+    pub fn available(&self) -> usize {
+        self.inner.semaphore.available_permits()
+    }
+
+    /// Stops leasing pages and closes every browser of the pool.
+    ///
+    /// # Errors
+    /// Returns the first error met while closing the browsers, after trying to close all of them.
+    pub async fn close(&self) -> Result<(), BrowserError> {
+        self.inner.semaphore.close();
+        let mut result = Ok(());
+        for browser in &self.inner.browsers {
+            let closed = browser.close().await;
+            if result.is_ok() {
+                result = closed;
+            }
+        }
+        result
+    }
+}
+
+// --------------------------------------------------
+
+/// A page leased from a `BrowserPool`, usable as a `Page`.
+///
+/// Call `release` to return it and wait for its user context to be removed. A lease
+/// dropped without being released is returned in the background.
+pub struct PooledPage {
+    page: Option<Page>,
+    session: WebDriverBiDiSession,
+    pool: Arc<PoolInner>,
+    index: usize,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl PooledPage {
+    /// Returns the page to the pool, removing its user context and with it the page's
+    /// tabs, cookies and storage.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `browser.removeUserContext` command fails.
+    pub async fn release(mut self) -> Result<(), BrowserError> {
+        let Some(page) = self.page.take() else {
+            return Ok(());
+        };
+        let permit = self.permit.take();
+        let result = reset(&mut self.session, &page).await;
+        self.pool.release(self.index);
+        drop(permit);
+        result
+    }
+}
+
+impl Deref for PooledPage {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.page.as_ref().expect("The page was already released")
+    }
+}
+
+impl Drop for PooledPage {
+    fn drop(&mut self) {
+        let Some(page) = self.page.take() else {
+            return;
+        };
+        let permit = self.permit.take();
+        let pool = self.pool.clone();
+        let index = self.index;
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            error!("No runtime to release page {} on drop", page.context());
+            pool.release(index);
+            return;
+        };
+        let mut session = self.session.clone();
+        runtime.spawn(async move {
+            if let Err(e) = reset(&mut session, &page).await {
+                error!("Releasing page {} failed: {}", page.context(), e);
+            }
+            pool.release(index);
+            drop(permit);
+        });
+    }
+}
+
+/// Discards the state of a leased page by removing its user context.
+async fn reset(session: &mut WebDriverBiDiSession, page: &Page) -> Result<(), BrowserError> {
+    debug!("Releasing page {}", page.context());
+    user_context::remove_user_context(session, page.user_context()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(browsers: usize) -> PoolInner {
+        PoolInner {
+            browsers: (0..browsers).map(|_| Browser::new("localhost", 4444)).collect(),
+            leased: Mutex::new(vec![0; browsers]),
+            semaphore: Arc::new(Semaphore::new(1)),
+        }
+    }
+
+    #[test]
+    fn least_leased_prefers_the_first_idle_browser() {
+        assert_eq!(least_leased(&[0, 0, 0]), 0);
+        assert_eq!(least_leased(&[2, 1, 1]), 1);
+        assert_eq!(least_leased(&[1, 3, 0]), 2);
+    }
+
+    #[test]
+    fn concurrent_leases_are_spread_over_the_browsers() {
+        let pool = pool(3);
+        std::thread::scope(|scope| {
+            for _ in 0..12 {
+                scope.spawn(|| pool.lease());
+            }
+        });
+        assert_eq!(*pool.leased.lock().unwrap(), [4, 4, 4]);
+
+        pool.release(1);
+        assert_eq!(pool.lease(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+use log::{debug, error};
+use webdriverbidi::model::script::{
+    AddPreloadScriptParameters, ChannelValue, RemovePreloadScriptParameters,
+};
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::error::BrowserError;
+
+// --------------------------------------------------
+
+/// Adds a script run before any page script in new documents.
+pub async fn add_preload_script(
+    session: &mut WebDriverBiDiSession,
+    function_declaration: &str,
+    arguments: Option<Vec<ChannelValue>>,
+    sandbox: Option<&str>,
+    contexts: Option<Vec<String>>,
+) -> Result<String, BrowserError> {
+    let add_params = AddPreloadScriptParameters::new(
+        function_declaration.to_string(),
+        arguments,
+        contexts,
+        None,
+        sandbox.map(str::to_string),
+    );
+    let rslt = session
+        .script_add_preload_script(add_params)
+        .await
+        .map_err(|e| BrowserError::command("script.addPreloadScript", e))?;
+    debug!("Preload script added: {}", rslt.script);
+    Ok(rslt.script)
+}
+
+/// Removes a preload script.
+pub async fn remove_preload_script(
+    session: &mut WebDriverBiDiSession,
+    id: &str,
+) -> Result<(), BrowserError> {
+    session
+        .script_remove_preload_script(RemovePreloadScriptParameters::new(id.to_string()))
+        .await
+        .map_err(|e| BrowserError::command("script.removePreloadScript", e))?;
+    debug!("Preload script removed: {}", id);
+    Ok(())
+}
+
+// --------------------------------------------------
+
+/// A handle to a preload script, which is removed when the handle is dropped.
+///
+/// Use `remove` to remove it explicitly and observe errors,
+/// or `detach` to keep the script for the rest of the session.
+pub struct PreloadScript {
+    session: WebDriverBiDiSession,
+    id: String,
+    attached: bool,
+}
+
+impl PreloadScript {
+    pub(crate) fn new(session: WebDriverBiDiSession, id: String) -> Self {
+        Self {
+            session,
+            id,
+            attached: true,
+        }
+    }
+
+    /// Returns the identifier of the preload script.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Removes the preload script.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Protocol` if the `script.removePreloadScript` command fails.
+    pub async fn remove(mut self) -> Result<(), BrowserError> {
+        self.attached = false;
+        remove_preload_script(&mut self.session, &self.id).await
+    }
+
+    /// Releases the handle without removing the preload script, returning its identifier.
+    pub fn detach(mut self) -> String {
+        self.attached = false;
+        std::mem::take(&mut self.id)
+    }
+}
+
+impl Drop for PreloadScript {
+    fn drop(&mut self) {
+        if !self.attached {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            error!("No runtime to remove preload script {} on drop", self.id);
+            return;
+        };
+        let mut session = self.session.clone();
+        let id = std::mem::take(&mut self.id);
+        runtime.spawn(async move {
+            if let Err(e) = remove_preload_script(&mut session, &id).await {
+                error!("{}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,181 @@
+use base64::prelude::*;
+use serde_json::{Map, Value, json};
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::command;
+use crate::error::BrowserError;
+
+// --------------------------------------------------
+
+/// The orientation of printed pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+impl Orientation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Portrait => "portrait",
+            Self::Landscape => "landscape",
+        }
+    }
+}
+
+/// Options for printing a page to PDF. Lengths are in centimeters.
+///
+/// Unset options use the browser defaults: US Letter portrait pages with 1cm margins,
+/// a scale of 1, no backgrounds, all pages and shrink-to-fit enabled.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::PrintOptions;
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let options = PrintOptions::new()
+///     .a4()
+///     .margins(1.5, 1.0, 1.5, 1.0)
+///     .background(true)
+///     .page_ranges(&["1-2", "4"]);
+/// browser.save_pdf("target/invoices/invoice-42.pdf", &options).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrintOptions {
+    page_size: Option<(f64, f64)>,
+    margins: Option<(f64, f64, f64, f64)>,
+    orientation: Option<Orientation>,
+    scale: Option<f64>,
+    background: Option<bool>,
+    page_ranges: Vec<String>,
+    shrink_to_fit: Option<bool>,
+}
+
+impl PrintOptions {
+    /// Creates options using the browser defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the page width and height.
+    pub fn page_size(mut self, width: f64, height: f64) -> Self {
+        self.page_size = Some((width, height));
+        self
+    }
+
+    /// Uses A4 pages (21 x 29.7cm).
+    pub fn a4(self) -> Self {
+        self.page_size(21.0, 29.7)
+    }
+
+    /// Uses US Letter pages (21.59 x 27.94cm).
+    pub fn letter(self) -> Self {
+        self.page_size(21.59, 27.94)
+    }
+
+    /// Sets the top, right, bottom and left margins.
+    pub fn margins(mut self, top: f64, right: f64, bottom: f64, left: f64) -> Self {
+        self.margins = Some((top, right, bottom, left));
+        self
+    }
+
+    /// Sets the page orientation.
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = Some(orientation);
+        self
+    }
+
+    /// Prints in landscape orientation.
+    pub fn landscape(self) -> Self {
+        self.orientation(Orientation::Landscape)
+    }
+
+    /// Sets the scale of the page content, between `0.1` and `2.0`.
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Sets whether background colors and images are printed.
+    pub fn background(mut self, background: bool) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    /// Restricts printing to page ranges, e.g. `["1-3", "5"]`.
+    pub fn page_ranges(mut self, ranges: &[&str]) -> Self {
+        self.page_ranges = ranges.iter().map(|range| range.to_string()).collect();
+        self
+    }
+
+    /// Sets whether content wider than the page is shrunk to fit.
+    pub fn shrink_to_fit(mut self, shrink_to_fit: bool) -> Self {
+        self.shrink_to_fit = Some(shrink_to_fit);
+        self
+    }
+
+    fn params(&self, context: &str) -> Value {
+        let mut params = Map::new();
+        params.insert("context".to_string(), Value::from(context));
+        if let Some((width, height)) = self.page_size {
+            let page = json!({ "width": width, "height": height });
+            params.insert("page".to_string(), page);
+        }
+        if let Some((top, right, bottom, left)) = self.margins {
+            let margin = json!({
+                "top": top,
+                "right": right,
+                "bottom": bottom,
+                "left": left,
+            });
+            params.insert("margin".to_string(), margin);
+        }
+        if let Some(orientation) = self.orientation {
+            params.insert("orientation".to_string(), Value::from(orientation.as_str()));
+        }
+        if let Some(scale) = self.scale {
+            params.insert("scale".to_string(), Value::from(scale));
+        }
+        if let Some(background) = self.background {
+            params.insert("background".to_string(), Value::from(background));
+        }
+        if !self.page_ranges.is_empty() {
+            let ranges = self
+                .page_ranges
+                .iter()
+                .map(|range| match range.trim().parse::<u64>() {
+                    Ok(page) => Value::from(page),
+                    Err(_) => Value::from(range.trim()),
+                })
+                .collect();
+            params.insert("pageRanges".to_string(), Value::Array(ranges));
+        }
+        if let Some(shrink_to_fit) = self.shrink_to_fit {
+            params.insert("shrinkToFit".to_string(), Value::from(shrink_to_fit));
+        }
+        Value::Object(params)
+    }
+}
+
+// --------------------------------------------------
+
+/// Prints the page of a top-level browsing context to PDF and returns the PDF bytes.
+pub async fn print_to_pdf(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    options: &PrintOptions,
+) -> Result<Vec<u8>, BrowserError> {
+    let result = command::send(session, "browsingContext.print", options.params(context))
+        .await
+        .map_err(|e| BrowserError::command("browsingContext.print", e))?;
+    let data = result
+        .get("data")
+        .and_then(Value::as_str)
+        .ok_or_else(|| BrowserError::Print("No data in the print result".to_string()))?;
+    BASE64_STANDARD
+        .decode(data)
+        .map_err(|e| BrowserError::Print(format!("Decoding the PDF failed: {}", e)))
+}
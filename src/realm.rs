@@ -0,0 +1,251 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::{debug, error};
+use serde_json::{Map, Value};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use webdriverbidi::model::session::SubscriptionRequest;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::command;
+use crate::error::BrowserError;
+use crate::events::EventDispatcher;
+
+// --------------------------------------------------
+
+const REALM_CREATED: &str = "script.realmCreated";
+const REALM_DESTROYED: &str = "script.realmDestroyed";
+
+// --------------------------------------------------
+
+/// The kind of a JavaScript realm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RealmType {
+    Window,
+    DedicatedWorker,
+    SharedWorker,
+    ServiceWorker,
+    Worker,
+    PaintWorklet,
+    AudioWorklet,
+    Worklet,
+}
+
+impl RealmType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Window => "window",
+            Self::DedicatedWorker => "dedicated-worker",
+            Self::SharedWorker => "shared-worker",
+            Self::ServiceWorker => "service-worker",
+            Self::Worker => "worker",
+            Self::PaintWorklet => "paint-worklet",
+            Self::AudioWorklet => "audio-worklet",
+            Self::Worklet => "worklet",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "window" => Some(Self::Window),
+            "dedicated-worker" => Some(Self::DedicatedWorker),
+            "shared-worker" => Some(Self::SharedWorker),
+            "service-worker" => Some(Self::ServiceWorker),
+            "worker" => Some(Self::Worker),
+            "paint-worklet" => Some(Self::PaintWorklet),
+            "audio-worklet" => Some(Self::AudioWorklet),
+            "worklet" => Some(Self::Worklet),
+            _ => None,
+        }
+    }
+}
+
+/// A JavaScript realm: the global scope of a document, sandbox or worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RealmInfo {
+    /// The identifier of the realm, used to target it with `evaluate_in_realm`.
+    pub realm: String,
+    /// The origin of the realm.
+    pub origin: String,
+    /// The kind of the realm.
+    pub realm_type: RealmType,
+    /// The browsing context of a window realm.
+    pub context: Option<String>,
+    /// The sandbox name of a window realm, `None` for the page realm.
+    pub sandbox: Option<String>,
+    /// The realms owning a dedicated worker.
+    pub owners: Vec<String>,
+}
+
+impl RealmInfo {
+    /// Parses a serialized `script.RealmInfo`.
+    fn from_json(info: &Value) -> Option<Self> {
+        let optional_string =
+            |key: &str| info.get(key).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            realm: info.get("realm")?.as_str()?.to_string(),
+            origin: optional_string("origin").unwrap_or_default(),
+            realm_type: RealmType::from_str(info.get("type")?.as_str()?)?,
+            context: optional_string("context"),
+            sandbox: optional_string("sandbox"),
+            owners: info
+                .get("owners")
+                .and_then(Value::as_array)
+                .map(|owners| {
+                    owners
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Restricts the realms returned by `Browser::realms`.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::{RealmFilter, RealmType};
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let workers = browser
+///     .realms(RealmFilter::default().realm_type(RealmType::ServiceWorker))
+///     .await?;
+/// for worker in workers {
+///     let href = browser.evaluate_in_realm(&worker.realm, "self.location.href").await?;
+///     println!("Service worker: {}", href);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RealmFilter {
+    context: Option<String>,
+    realm_type: Option<RealmType>,
+}
+
+impl RealmFilter {
+    /// Only returns the realms associated with a browsing context.
+    pub fn context(mut self, context: &str) -> Self {
+        self.context = Some(context.to_string());
+        self
+    }
+
+    /// Only returns the realms of a kind.
+    pub fn realm_type(mut self, realm_type: RealmType) -> Self {
+        self.realm_type = Some(realm_type);
+        self
+    }
+
+    fn params(&self) -> Value {
+        let mut params = Map::new();
+        if let Some(context) = &self.context {
+            params.insert("context".to_string(), Value::from(context.as_str()));
+        }
+        if let Some(realm_type) = self.realm_type {
+            params.insert("type".to_string(), Value::from(realm_type.as_str()));
+        }
+        Value::Object(params)
+    }
+}
+
+/// A realm lifecycle event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RealmEvent {
+    /// A realm was created.
+    Created(RealmInfo),
+    /// A realm was destroyed.
+    Destroyed {
+        /// The identifier of the destroyed realm.
+        realm: String,
+    },
+}
+
+/// Receives realm lifecycle events.
+pub type RealmEventReceiver = UnboundedReceiver<RealmEvent>;
+
+/// Routes `script.realmCreated` and `script.realmDestroyed` events to their receivers.
+#[derive(Default)]
+pub struct RealmEventRegistry {
+    senders: Mutex<Vec<UnboundedSender<RealmEvent>>>,
+    listening: AtomicBool,
+}
+
+impl RealmEventRegistry {
+    fn dispatch(&self, event: RealmEvent) {
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.retain(|sender| sender.send(event.clone()).is_ok());
+        }
+    }
+}
+
+// --------------------------------------------------
+
+/// Returns the realms matching the filter.
+pub async fn get_realms(
+    session: &mut WebDriverBiDiSession,
+    filter: &RealmFilter,
+) -> Result<Vec<RealmInfo>, BrowserError> {
+    let result = command::send(session, "script.getRealms", filter.params())
+        .await
+        .map_err(|e| BrowserError::command("script.getRealms", e))?;
+
+    let realms = result
+        .get("realms")
+        .and_then(Value::as_array)
+        .map(|realms| realms.iter().filter_map(RealmInfo::from_json).collect())
+        .unwrap_or_default();
+    debug!("Realms: {:?}", realms);
+    Ok(realms)
+}
+
+/// Returns a receiver of realm lifecycle events, subscribing to them the first time.
+pub async fn realm_events(
+    session: &mut WebDriverBiDiSession,
+    events: &EventDispatcher,
+    registry: &Arc<RealmEventRegistry>,
+) -> Result<RealmEventReceiver, BrowserError> {
+    if !registry.listening.swap(true, Ordering::SeqCst) {
+        debug!("Listening to realm events");
+        let created_registry = registry.clone();
+        let created_handler = events.subscribe(REALM_CREATED, move |event| {
+            match event.get("params").and_then(RealmInfo::from_json) {
+                Some(info) => created_registry.dispatch(RealmEvent::Created(info)),
+                None => error!("Unexpected realm created event: {:?}", event),
+            }
+            true
+        });
+
+        let destroyed_registry = registry.clone();
+        let destroyed_handler = events.subscribe(REALM_DESTROYED, move |event| {
+            match event.pointer("/params/realm").and_then(Value::as_str) {
+                Some(realm) => destroyed_registry.dispatch(RealmEvent::Destroyed {
+                    realm: realm.to_string(),
+                }),
+                None => error!("Unexpected realm destroyed event: {:?}", event),
+            }
+            true
+        });
+
+        let subscription_params = SubscriptionRequest::new(
+            vec![REALM_CREATED.to_string(), REALM_DESTROYED.to_string()],
+            None,
+            None,
+        );
+        if let Err(e) = session.session_subscribe(subscription_params).await {
+            events.unsubscribe(created_handler);
+            events.unsubscribe(destroyed_handler);
+            registry.listening.store(false, Ordering::SeqCst);
+            return Err(BrowserError::command("session.subscribe", e));
+        }
+    }
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if let Ok(mut senders) = registry.senders.lock() {
+        senders.push(sender);
+    }
+    Ok(receiver)
+}
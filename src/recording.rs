@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, error};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::{apng, files};
+use crate::error::BrowserError;
+use crate::screenshot::{self, ImageFormat, ScreenshotOptions};
+
+// --------------------------------------------------
+
+/// Where a recording is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingOutput {
+    /// Numbered PNG files, `frame-00001.png`, `frame-00002.png`... in a directory.
+    Frames(PathBuf),
+    /// A single animated PNG file.
+    Apng(PathBuf),
+}
+
+/// Options for recording a browsing context.
+///
+/// # Example
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use autom8::RecordingOptions;
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let options = RecordingOptions::apng("target/recordings/checkout.png").max_frames(200);
+/// browser.start_recording(Duration::from_millis(250), options)?;
+/// // ... steps of a flaky test; frames are saved automatically if it panics
+/// let files = browser.stop_recording()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingOptions {
+    output: RecordingOutput,
+    max_frames: usize,
+    screenshot: ScreenshotOptions,
+    save_on_failure: bool,
+}
+
+impl RecordingOptions {
+    /// Records numbered PNG files in a directory.
+    pub fn frames(dir: impl Into<PathBuf>) -> Self {
+        Self::new(RecordingOutput::Frames(dir.into()))
+    }
+
+    /// Records a single animated PNG file.
+    pub fn apng(path: impl Into<PathBuf>) -> Self {
+        Self::new(RecordingOutput::Apng(path.into()))
+    }
+
+    /// Creates the default options for an output: at most 300 frames of the viewport,
+    /// saved automatically when the thread panics.
+    pub fn new(output: RecordingOutput) -> Self {
+        Self {
+            output,
+            max_frames: 300,
+            screenshot: ScreenshotOptions::new().viewport(),
+            save_on_failure: true,
+        }
+    }
+
+    /// Sets the maximum number of frames kept. Once reached, the oldest frames are discarded.
+    pub fn max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = max_frames.max(1);
+        self
+    }
+
+    /// Sets the origin and clip of the captured frames. Frames are always captured as PNG.
+    pub fn screenshot(mut self, options: ScreenshotOptions) -> Self {
+        self.screenshot = options;
+        self
+    }
+
+    /// Sets whether the frames are saved when the recorder is dropped during a panic,
+    /// e.g. when a test assertion fails while recording.
+    pub fn save_on_failure(mut self, save_on_failure: bool) -> Self {
+        self.save_on_failure = save_on_failure;
+        self
+    }
+}
+
+// --------------------------------------------------
+
+/// Captures screenshots of a browsing context in a background task.
+pub struct Recorder {
+    frames: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    options: RecordingOptions,
+    interval: Duration,
+    task: JoinHandle<()>,
+    stopped: bool,
+}
+
+impl Recorder {
+    /// Starts capturing a screenshot of the context every `interval`.
+    pub fn start(
+        session: &WebDriverBiDiSession,
+        context: &str,
+        sandbox: Option<&str>,
+        interval: Duration,
+        options: RecordingOptions,
+    ) -> Self {
+        let frames = Arc::new(Mutex::new(VecDeque::new()));
+        let task_frames = frames.clone();
+        let mut session = session.clone();
+        debug!("Recording context {} every {:?}", context, interval);
+        let context = context.to_string();
+        let sandbox = sandbox.map(str::to_string);
+        let screenshot_options = options.screenshot.clone().format(ImageFormat::Png);
+        let max_frames = options.max_frames;
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // A slow capture delays the next ones instead of being followed by a burst
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let capture = screenshot::take_screenshot(
+                    &mut session,
+                    context.clone(),
+                    sandbox.as_deref(),
+                    &screenshot_options,
+                )
+                .await;
+                let frame = match capture.and_then(|data| screenshot::decode(&data)) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        debug!("Skipping recording frame: {}", e);
+                        continue;
+                    }
+                };
+                if let Ok(mut frames) = task_frames.lock() {
+                    if frames.len() >= max_frames {
+                        frames.pop_front();
+                    }
+                    frames.push_back(frame);
+                }
+            }
+        });
+        Self {
+            frames,
+            options,
+            interval,
+            task,
+            stopped: false,
+        }
+    }
+
+    /// Stops capturing and writes the frames, returning the paths of the written files.
+    pub fn stop(mut self) -> Result<Vec<PathBuf>, BrowserError> {
+        self.task.abort();
+        self.stopped = true;
+        self.save()
+    }
+
+    fn save(&self) -> Result<Vec<PathBuf>, BrowserError> {
+        let frames: Vec<Vec<u8>> = match self.frames.lock() {
+            Ok(mut frames) => frames.drain(..).collect(),
+            Err(_) => Vec::new(),
+        };
+        if frames.is_empty() {
+            return Err(BrowserError::Recording("No frame was recorded".to_string()));
+        }
+        match &self.options.output {
+            RecordingOutput::Frames(dir) => frames
+                .iter()
+                .enumerate()
+                .map(|(index, frame)| {
+                    let path = dir.join(format!("frame-{:05}.png", index + 1));
+                    files::write_file(&path, frame, BrowserError::Recording).map(|_| path)
+                })
+                .collect(),
+            RecordingOutput::Apng(path) => {
+                let delay_ms = self.interval.as_millis() as u64;
+                let animation = apng::encode(&frames, delay_ms).ok_or_else(|| {
+                    BrowserError::Recording("The recorded frames are not valid PNGs".to_string())
+                })?;
+                files::write_file(path, &animation, BrowserError::Recording)?;
+                Ok(vec![path.clone()])
+            }
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.task.abort();
+        if self.stopped || !self.options.save_on_failure || !std::thread::panicking() {
+            return;
+        }
+        match self.save() {
+            Ok(paths) => error!("Recording saved after a failure: {:?}", paths),
+            Err(e) => error!("Saving the recording after a failure failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use serde_json::{Value, json};
+
+// --------------------------------------------------
+
+/// Converts a serialized `script.RemoteValue` into plain JSON.
+///
+/// Primitives map to their JSON counterparts, arrays and sets to arrays, objects and
+/// maps to objects, dates to strings and regular expressions to `/pattern/flags` strings.
+/// Values without a JSON representation (functions, nodes, windows, special numbers...)
+/// and values beyond the serialization depth become `null`.
+pub fn to_json(remote_value: &Value) -> Value {
+    let value = remote_value.get("value");
+    match remote_value.get("type").and_then(Value::as_str) {
+        Some("string") | Some("bigint") | Some("date") => value.cloned().unwrap_or(Value::Null),
+        Some("boolean") => value.cloned().unwrap_or(Value::Null),
+        Some("number") => match value {
+            Some(Value::Number(number)) => Value::Number(number.clone()),
+            Some(Value::String(special)) if special == "-0" => Value::from(0),
+            _ => Value::Null,
+        },
+        Some("array") | Some("set") | Some("nodelist") | Some("htmlcollection") => {
+            match value.and_then(Value::as_array) {
+                Some(items) => Value::Array(items.iter().map(to_json).collect()),
+                None => Value::Null,
+            }
+        }
+        Some("object") | Some("map") => match value.and_then(Value::as_array) {
+            Some(entries) => Value::Object(entries.iter().filter_map(entry_to_json).collect()),
+            None => Value::Null,
+        },
+        Some("regexp") => match value {
+            Some(regexp) => Value::from(format!(
+                "/{}/{}",
+                regexp.get("pattern").and_then(Value::as_str).unwrap_or_default(),
+                regexp.get("flags").and_then(Value::as_str).unwrap_or_default()
+            )),
+            None => Value::Null,
+        },
+        _ => Value::Null,
+    }
+}
+
+/// Converts a `[key, value]` mapping entry, where the key is either a string
+/// or a serialized remote value.
+fn entry_to_json(entry: &Value) -> Option<(String, Value)> {
+    let key = match entry.get(0)? {
+        Value::String(key) => key.clone(),
+        remote_key => match to_json(remote_key) {
+            Value::String(key) => key,
+            other => other.to_string(),
+        },
+    };
+    Some((key, to_json(entry.get(1)?)))
+}
+
+/// Converts plain JSON into a serialized `script.LocalValue`.
+pub fn from_json(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({ "type": "null" }),
+        Value::Bool(b) => json!({ "type": "boolean", "value": b }),
+        Value::Number(n) => json!({ "type": "number", "value": n }),
+        Value::String(s) => json!({ "type": "string", "value": s }),
+        Value::Array(items) => json!({
+            "type": "array",
+            "value": items.iter().map(from_json).collect::<Vec<_>>(),
+        }),
+        Value::Object(entries) => json!({
+            "type": "object",
+            "value": entries
+                .iter()
+                .map(|(key, value)| json!([key, from_json(value)]))
+                .collect::<Vec<_>>(),
+        }),
+    }
+}
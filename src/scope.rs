@@ -0,0 +1,340 @@
+use std::future::Future;
+use std::path::Path;
+
+use log::debug;
+use serde_json::Value;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::browser::Browser;
+use crate::element::{self, Element};
+use crate::error::BrowserError;
+use crate::frame::{self, Frame};
+use crate::handle::{self, JsHandle};
+use crate::print::{self, PrintOptions};
+use crate::screenshot::{self, ScreenshotOptions};
+use crate::visual::{self, ScreenshotMatchOptions};
+use crate::{assertions, extract, files, input, local_storage, nav, script};
+
+// --------------------------------------------------
+
+/// The browsing context the actions of a `Browser`, `Page` or `Frame` run against.
+///
+/// Every action first checks that the browser is still usable, reconnecting with its
+/// reconnect policy if the connection was lost, so pages and frames stop sending
+/// commands once their browser is closed or disconnected. Failed actions save the
+/// failure artifacts of the browser's artifact policy, captured from this context.
+pub(crate) struct ContextScope<'a> {
+    browser: &'a Browser,
+    // `None` for the current browsing context of the browser
+    context: Option<&'a str>,
+    sandbox: Option<String>,
+    // Whether the context is a frame, captured from its top-level page
+    frame: bool,
+}
+
+impl<'a> ContextScope<'a> {
+    pub fn new(browser: &'a Browser, context: Option<&'a str>, sandbox: Option<String>) -> Self {
+        Self {
+            browser,
+            context,
+            sandbox,
+            frame: false,
+        }
+    }
+
+    /// Returns the scope of the actions run against a frame.
+    pub fn for_frame(browser: &'a Browser, context: &'a str, sandbox: Option<String>) -> Self {
+        Self {
+            frame: true,
+            ..Self::new(browser, Some(context), sandbox)
+        }
+    }
+
+    /// Returns a handle to the session of the browser, current even after a reconnection.
+    fn session(&self) -> WebDriverBiDiSession {
+        self.browser.webdriverbidi_session()
+    }
+
+    /// Returns a handle to the session of the browser, once it is usable.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection lost.
+    pub async fn connected_session(&self) -> Result<WebDriverBiDiSession, BrowserError> {
+        self.browser.ensure_connected().await?;
+        Ok(self.session())
+    }
+
+    /// Returns the browsing context to run an action against.
+    ///
+    /// # Errors
+    /// Returns a `BrowserError::Disconnected` if the browser is closed or its connection
+    /// lost, or a `BrowserError::Navigation` if the browser has no current browsing context.
+    pub async fn context(&self) -> Result<String, BrowserError> {
+        match self.context {
+            Some(context) => {
+                self.browser.ensure_connected().await?;
+                Ok(context.to_string())
+            }
+            None => self.browser.active_context().await,
+        }
+    }
+
+    /// Runs an action, saving the failure artifacts of its error if the browser has an
+    /// artifact policy.
+    async fn act<T>(
+        &self,
+        action: &str,
+        locator: Option<&str>,
+        future: impl Future<Output = Result<T, BrowserError>>,
+    ) -> Result<T, BrowserError> {
+        self.browser
+            .act(self.context, self.sandbox.as_deref(), action, locator, future)
+            .await
+    }
+}
+
+// Frames
+impl ContextScope<'_> {
+    pub async fn frames(&self) -> Result<Vec<Frame>, BrowserError> {
+        self.act("frames", None, async {
+            let ctx = self.context().await?;
+            let infos = frame::get_frames(&mut self.session(), &ctx, None).await?;
+            Ok(infos
+                .into_iter()
+                .map(|info| Frame::from_info(self.browser.clone(), self.sandbox.clone(), info))
+                .collect())
+        })
+        .await
+    }
+
+    pub async fn frame(&self, locator: &str) -> Result<Frame, BrowserError> {
+        self.act("frame", Some(locator), async {
+            let ctx = self.context().await?;
+            let info = frame::get_frame(&mut self.session(), &ctx, self.sandbox.as_deref(), locator).await?;
+            Ok(Frame::from_info(self.browser.clone(), self.sandbox.clone(), info))
+        })
+        .await
+    }
+}
+
+// Navigation
+impl ContextScope<'_> {
+    pub async fn load(&self, url: &str) -> Result<(), BrowserError> {
+        self.act("load", None, async {
+            let ctx = self.context().await?;
+            debug!("Navigating {} to URL: {}", ctx, url);
+            nav::load(&mut self.session(), ctx, url).await?;
+            debug!("Navigation to URL: {} completed successfully", url);
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn go_back(&self) -> Result<(), BrowserError> {
+        self.act("go_back", None, async {
+            nav::go_back(&mut self.session(), self.context().await?).await
+        })
+        .await
+    }
+
+    pub async fn go_forward(&self) -> Result<(), BrowserError> {
+        self.act("go_forward", None, async {
+            nav::go_forward(&mut self.session(), self.context().await?).await
+        })
+        .await
+    }
+
+    pub async fn reload(&self) -> Result<(), BrowserError> {
+        self.act("reload", None, async {
+            nav::reload(&mut self.session(), self.context().await?).await
+        })
+        .await
+    }
+
+    pub async fn wait_for_page_load(&self, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.act("wait_for_page_load", None, async {
+            let ctx = self.context().await?;
+            nav::wait_for_page_load(&mut self.session(), ctx, self.sandbox.as_deref(), timeout_ms).await
+        })
+        .await
+    }
+}
+
+// JavaScript evaluation
+impl ContextScope<'_> {
+    /// Evaluates an expression in the page realm, or in `sandbox` if any.
+    pub async fn evaluate(&self, expression: &str, sandbox: Option<&str>) -> Result<Value, BrowserError> {
+        self.act("evaluate", None, async {
+            let ctx = self.context().await?;
+            let target = script::context_target(&ctx, sandbox);
+            script::evaluate(&mut self.session(), target, expression).await
+        })
+        .await
+    }
+
+    pub async fn call_function(&self, function_declaration: &str, arguments: &[Value]) -> Result<Value, BrowserError> {
+        self.act("call_function", None, async {
+            let ctx = self.context().await?;
+            let target = script::context_target(&ctx, None);
+            script::call_function(&mut self.session(), target, function_declaration, arguments).await
+        })
+        .await
+    }
+
+    pub async fn evaluate_handle(&self, expression: &str) -> Result<JsHandle, BrowserError> {
+        self.act("evaluate_handle", None, async {
+            let ctx = self.context().await?;
+            let target = script::context_target(&ctx, None);
+            handle::evaluate_handle(&mut self.session(), target, expression).await
+        })
+        .await
+    }
+}
+
+// Screenshots and PDF printing
+impl ContextScope<'_> {
+    /// Takes a screenshot and returns the data as a base64-encoded string.
+    pub async fn screenshot(&self, options: &ScreenshotOptions) -> Result<String, BrowserError> {
+        self.act("screenshot", None, self.capture(options)).await
+    }
+
+    pub async fn screenshot_bytes(&self, options: &ScreenshotOptions) -> Result<Vec<u8>, BrowserError> {
+        self.act("screenshot_bytes", None, async {
+            let data = self.capture(options).await?;
+            screenshot::decode(&data)
+        })
+        .await
+    }
+
+    pub async fn save_screenshot(&self, path: &Path, options: &ScreenshotOptions) -> Result<(), BrowserError> {
+        self.act("save_screenshot", None, async {
+            let options = screenshot::options_for_path(path, options)?;
+            let data = self.capture(&options).await?;
+            files::write_file(path, &screenshot::decode(&data)?, BrowserError::Screenshot)
+        })
+        .await
+    }
+
+    async fn capture(&self, options: &ScreenshotOptions) -> Result<String, BrowserError> {
+        let ctx = self.context().await?;
+        if self.frame {
+            return screenshot::take_frame_screenshot(&mut self.session(), &ctx, self.sandbox.as_deref(), options).await;
+        }
+        screenshot::take_screenshot(&mut self.session(), ctx, self.sandbox.as_deref(), options).await
+    }
+
+    pub async fn assert_screenshot_matches(&self, name: &str, options: &ScreenshotMatchOptions) -> Result<(), BrowserError> {
+        self.act("assert_screenshot_matches", None, async {
+            let ctx = self.context().await?;
+            visual::assert_screenshot_matches(&mut self.session(), &ctx, self.sandbox.as_deref(), name, options).await
+        })
+        .await
+    }
+
+    pub async fn print_to_pdf(&self, options: &PrintOptions) -> Result<Vec<u8>, BrowserError> {
+        self.act("print_to_pdf", None, self.print(options)).await
+    }
+
+    pub async fn save_pdf(&self, path: &Path, options: &PrintOptions) -> Result<(), BrowserError> {
+        self.act("save_pdf", None, async {
+            let bytes = self.print(options).await?;
+            files::write_file(path, &bytes, BrowserError::Print)
+        })
+        .await
+    }
+
+    async fn print(&self, options: &PrintOptions) -> Result<Vec<u8>, BrowserError> {
+        let ctx = self.context().await?;
+        print::print_to_pdf(&mut self.session(), &ctx, options).await
+    }
+}
+
+// Local storage
+impl ContextScope<'_> {
+    pub async fn set_local_storage_value(&self, key: &str, value: &str) -> Result<(), BrowserError> {
+        self.act("set_local_storage_value", None, async {
+            let ctx = self.context().await?;
+            local_storage::set_local_storage(&mut self.session(), &ctx, self.sandbox.as_deref(), key, value).await
+        })
+        .await
+    }
+
+    pub async fn get_local_storage_value(&self, key: &str) -> Result<Option<String>, BrowserError> {
+        self.act("get_local_storage_value", None, async {
+            let ctx = self.context().await?;
+            local_storage::get_local_storage(&mut self.session(), &ctx, self.sandbox.as_deref(), key).await
+        })
+        .await
+    }
+}
+
+// Elements and assertions
+impl ContextScope<'_> {
+    pub async fn element(&self, selector: &str) -> Result<Element, BrowserError> {
+        self.act("element", Some(selector), async {
+            let ctx = self.context().await?;
+            element::find_element(&mut self.session(), &ctx, self.sandbox.as_deref(), selector).await
+        })
+        .await
+    }
+
+    pub async fn assert_element_present(&self, selector: &str) -> Result<bool, BrowserError> {
+        self.act("assert_element_present", Some(selector), async {
+            let ctx = self.context().await?;
+            assertions::assert_element_present(&mut self.session(), &ctx, self.sandbox.as_deref(), selector).await
+        })
+        .await
+    }
+}
+
+// Input/Interaction and extraction
+impl ContextScope<'_> {
+    pub async fn click_element(&self, selector: &str) -> Result<(), BrowserError> {
+        self.act("click_element", Some(selector), async {
+            let ctx = self.context().await?;
+            input::click_element(&mut self.session(), &ctx, self.sandbox.as_deref(), selector).await
+        })
+        .await
+    }
+
+    pub async fn wait_and_click_element(&self, selector: &str, timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.act("wait_and_click_element", Some(selector), async {
+            let ctx = self.context().await?;
+            input::wait_and_click_element(&mut self.session(), &ctx, self.sandbox.as_deref(), selector, timeout_ms).await
+        })
+        .await
+    }
+
+    /// Clicks an element and waits for the page to load, each step saving the failure
+    /// artifacts of its own error.
+    pub async fn click_and_wait(&self, selector: &str, page_load_timeout_ms: Option<u64>) -> Result<(), BrowserError> {
+        self.click_element(selector).await?;
+        self.wait_for_page_load(page_load_timeout_ms).await
+    }
+
+    pub async fn extract_inner_html(&self, selector: &str) -> Result<String, BrowserError> {
+        self.act("extract_inner_html", Some(selector), async {
+            let ctx = self.context().await?;
+            extract::extract_inner_html(&mut self.session(), &ctx, self.sandbox.as_deref(), selector).await
+        })
+        .await
+    }
+
+    pub async fn extract_inner_text(&self, selector: &str) -> Result<String, BrowserError> {
+        self.act("extract_inner_text", Some(selector), async {
+            let ctx = self.context().await?;
+            extract::extract_inner_text(&mut self.session(), &ctx, self.sandbox.as_deref(), selector).await
+        })
+        .await
+    }
+
+    pub async fn extract_attribute(&self, selector: &str, attribute: &str) -> Result<Option<String>, BrowserError> {
+        self.act("extract_attribute", Some(selector), async {
+            let ctx = self.context().await?;
+            extract::extract_attribute(&mut self.session(), &ctx, self.sandbox.as_deref(), selector, attribute).await
+        })
+        .await
+    }
+}
@@ -1,11 +1,159 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use base64::prelude::*;
+use serde_json::{Value, json};
 use webdriverbidi::model::browsing_context::{
-    CaptureScreenshotParameters, CaptureScreenshotParametersOrigin, ImageFormat,
+    BoxClipRectangle, CaptureScreenshotParameters, CaptureScreenshotParametersOrigin, ClipRectangle,
+    ElementClipRectangle,
 };
+use webdriverbidi::model::script::SharedReference;
 use webdriverbidi::session::WebDriverBiDiSession;
 
 // --------------------------------------------------
 
 use crate::error::BrowserError;
+use crate::{command, element, frame, remote_value, script};
+
+// --------------------------------------------------
+
+/// The area a screenshot is taken relative to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScreenshotOrigin {
+    /// The visible part of the page.
+    Viewport,
+    /// The whole page, including the parts scrolled out of view.
+    #[default]
+    Document,
+}
+
+/// The image format of a screenshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    /// JPEG, with a quality between `0.0` and `1.0`.
+    Jpeg { quality: Option<f32> },
+    /// WebP, with a quality between `0.0` and `1.0`.
+    Webp { quality: Option<f32> },
+}
+
+impl ImageFormat {
+    /// Returns the MIME type of the format, e.g. `"image/png"`.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg { .. } => "image/jpeg",
+            Self::Webp { .. } => "image/webp",
+        }
+    }
+
+    /// Returns the format matching the extension of a file path (`png`, `jpg`, `jpeg` or `webp`).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg { quality: None }),
+            "webp" => Some(Self::Webp { quality: None }),
+            _ => None,
+        }
+    }
+
+    fn to_bidi(self) -> webdriverbidi::model::browsing_context::ImageFormat {
+        let quality = match self {
+            Self::Png => None,
+            Self::Jpeg { quality } | Self::Webp { quality } => quality,
+        };
+        webdriverbidi::model::browsing_context::ImageFormat::new(self.mime_type().to_string(), quality)
+    }
+}
+
+/// The part of the page a screenshot is clipped to.
+#[derive(Debug, Clone, PartialEq)]
+enum Clip {
+    Box { x: f32, y: f32, width: f32, height: f32 },
+    Element(String),
+    SharedId { context: String, shared_id: String },
+}
+
+/// Options for taking a screenshot.
+///
+/// The default is a PNG screenshot of the whole document.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::{ImageFormat, ScreenshotOptions};
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let options = ScreenshotOptions::new()
+///     .viewport()
+///     .format(ImageFormat::Jpeg { quality: Some(0.8) })
+///     .element("#checkout-summary");
+/// let data = browser.take_screenshot_with_options(&options).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScreenshotOptions {
+    origin: ScreenshotOrigin,
+    format: ImageFormat,
+    clip: Option<Clip>,
+}
+
+impl ScreenshotOptions {
+    /// Creates the default options: a PNG screenshot of the whole document.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the area the screenshot is taken relative to.
+    pub fn origin(mut self, origin: ScreenshotOrigin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Captures the visible part of the page only.
+    pub fn viewport(self) -> Self {
+        self.origin(ScreenshotOrigin::Viewport)
+    }
+
+    /// Captures the whole page, including the parts scrolled out of view.
+    pub fn full_page(self) -> Self {
+        self.origin(ScreenshotOrigin::Document)
+    }
+
+    /// Sets the image format.
+    pub fn format(mut self, format: ImageFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Clips the screenshot to a box, in CSS pixels relative to the origin.
+    pub fn clip(mut self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.clip = Some(Clip::Box { x, y, width, height });
+        self
+    }
+
+    /// Clips the screenshot to the element matching a selector, which may use the
+    /// frame-piercing syntax, e.g. `iframe#checkout |> .summary`.
+    pub fn element(mut self, selector: &str) -> Self {
+        self.clip = Some(Clip::Element(selector.to_string()));
+        self
+    }
+
+    /// Returns the image format.
+    pub fn image_format(&self) -> ImageFormat {
+        self.format
+    }
+
+    /// Clips the screenshot to the node with the given shared id in `context`.
+    pub(crate) fn shared_id(mut self, context: &str, shared_id: &str) -> Self {
+        self.clip = Some(Clip::SharedId {
+            context: context.to_string(),
+            shared_id: shared_id.to_string(),
+        });
+        self
+    }
+}
 
 // --------------------------------------------------
 
@@ -13,23 +161,209 @@ use crate::error::BrowserError;
 pub async fn take_screenshot(
     session: &mut WebDriverBiDiSession,
     context: String,
+    sandbox: Option<&str>,
+    options: &ScreenshotOptions,
 ) -> Result<String, BrowserError> {
-    let origin = Some(CaptureScreenshotParametersOrigin::Document);
-    let format = Some(ImageFormat {
-        // TODO - Strongly typed image format
-        image_format_type: "png".to_owned(),
-        quality: None,
+    let origin = Some(match options.origin {
+        ScreenshotOrigin::Viewport => CaptureScreenshotParametersOrigin::Viewport,
+        ScreenshotOrigin::Document => CaptureScreenshotParametersOrigin::Document,
     });
+    let format = Some(options.format.to_bidi());
+    let clip = match &options.clip {
+        Some(Clip::Box { x, y, width, height }) => Some(ClipRectangle::BoxClipRectangle(
+            BoxClipRectangle::new(*x, *y, *width, *height),
+        )),
+        Some(Clip::Element(selector)) => {
+            let (element_context, element_selector) = frame::resolve_locator(session, &context, sandbox, selector).await?;
+            let shared_id = element::shared_id(session, &element_context, sandbox, &element_selector)
+                .await
+                .map_err(|e| e.for_locator(selector))?;
+            Some(element_clip(session, &context, &element_context, sandbox, shared_id, options.origin).await?)
+        }
+        Some(Clip::SharedId { context: element_context, shared_id }) => {
+            Some(element_clip(session, &context, element_context, sandbox, shared_id.clone(), options.origin).await?)
+        }
+        None => None,
+    };
     let params = CaptureScreenshotParameters {
         context,
         origin,
         format,
-        clip: None,
+        clip,
     };
     let rslt = session
         .browsing_context_capture_screenshot(params)
         .await
-        .map_err(|e| BrowserError::Screenshot(format!("Taking the screenshot failed: {}", e)))?;
+        .map_err(|e| BrowserError::command("browsingContext.captureScreenshot", e))?;
 
     Ok(rslt.data)
 }
+
+/// Takes a screenshot of a frame from its top-level page, as `browsingContext.captureScreenshot`
+/// only captures top-level contexts. Without a clip, the viewport of the frame is captured;
+/// clip boxes are relative to that viewport.
+pub async fn take_frame_screenshot(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    options: &ScreenshotOptions,
+) -> Result<String, BrowserError> {
+    let top = frame::top_level_context(session, context).await?;
+    if top == context {
+        return take_screenshot(session, top, sandbox, options).await;
+    }
+
+    let clip = match &options.clip {
+        Some(Clip::Element(selector)) => {
+            let (element_context, element_selector) = frame::resolve_locator(session, context, sandbox, selector).await?;
+            let shared_id = element::shared_id(session, &element_context, sandbox, &element_selector)
+                .await
+                .map_err(|e| e.for_locator(selector))?;
+            Clip::SharedId {
+                context: element_context,
+                shared_id,
+            }
+        }
+        Some(clip @ Clip::SharedId { .. }) => clip.clone(),
+        Some(Clip::Box { x, y, width, height }) => {
+            let [x, y, width, height] = [*x, *y, *width, *height].map(f64::from);
+            frame_clip(session, &top, context, sandbox, options.origin, [x, y, width, height]).await?
+        }
+        None => {
+            let target = script::context_target(context, sandbox);
+            let size = script::evaluate(
+                session,
+                target,
+                "[document.documentElement.clientWidth, document.documentElement.clientHeight]",
+            )
+            .await?;
+            let number = |index: usize| size.get(index).and_then(Value::as_f64).unwrap_or_default();
+            frame_clip(session, &top, context, sandbox, options.origin, [0.0, 0.0, number(0), number(1)]).await?
+        }
+    };
+    let options = ScreenshotOptions {
+        clip: Some(clip),
+        ..options.clone()
+    };
+    take_screenshot(session, top, sandbox, &options).await
+}
+
+/// Translates a box in the viewport of the frame `context` to a clip of a screenshot of
+/// its top-level context `top`.
+async fn frame_clip(
+    session: &mut WebDriverBiDiSession,
+    top: &str,
+    context: &str,
+    sandbox: Option<&str>,
+    origin: ScreenshotOrigin,
+    [x, y, width, height]: [f64; 4],
+) -> Result<Clip, BrowserError> {
+    let (frame_x, frame_y) = frame::frame_offset(session, context, top, sandbox).await?;
+    let (scroll_x, scroll_y) = scroll(session, top, sandbox, origin).await?;
+    Ok(Clip::Box {
+        x: (x + frame_x + scroll_x) as f32,
+        y: (y + frame_y + scroll_y) as f32,
+        width: width as f32,
+        height: height as f32,
+    })
+}
+
+/// Returns the scroll position of `context` for a screenshot relative to the document,
+/// or zero relative to the viewport.
+async fn scroll(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    origin: ScreenshotOrigin,
+) -> Result<(f64, f64), BrowserError> {
+    match origin {
+        ScreenshotOrigin::Viewport => Ok((0.0, 0.0)),
+        ScreenshotOrigin::Document => {
+            let target = script::context_target(context, sandbox);
+            let scroll = script::evaluate(session, target, "[window.scrollX, window.scrollY]").await?;
+            let number = |index: usize| scroll.get(index).and_then(Value::as_f64).unwrap_or_default();
+            Ok((number(0), number(1)))
+        }
+    }
+}
+
+/// Returns the clip of an element of `element_context` in a screenshot of `context`.
+///
+/// `browsingContext.captureScreenshot` only clips to elements of the captured document,
+/// so the box of an element of a nested frame is translated by the offsets of the frames.
+async fn element_clip(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    element_context: &str,
+    sandbox: Option<&str>,
+    shared_id: String,
+    origin: ScreenshotOrigin,
+) -> Result<ClipRectangle, BrowserError> {
+    if element_context == context {
+        return Ok(ClipRectangle::ElementClipRectangle(ElementClipRectangle::new(
+            SharedReference {
+                shared_id,
+                handle: None,
+                extensible: HashMap::new(),
+            },
+        )));
+    }
+
+    let (frame_x, frame_y) = frame::frame_offset(session, element_context, context, sandbox).await?;
+    let [x, y, width, height] = element_box(session, element_context, sandbox, &shared_id).await?;
+    let (scroll_x, scroll_y) = scroll(session, context, sandbox, origin).await?;
+    Ok(ClipRectangle::BoxClipRectangle(BoxClipRectangle::new(
+        (x + frame_x + scroll_x) as f32,
+        (y + frame_y + scroll_y) as f32,
+        width as f32,
+        height as f32,
+    )))
+}
+
+/// Returns the box of an element in the viewport of its document.
+async fn element_box(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    shared_id: &str,
+) -> Result<[f64; 4], BrowserError> {
+    let params = json!({
+        "functionDeclaration": "(element) => { const r = element.getBoundingClientRect(); return [r.left, r.top, r.width, r.height]; }",
+        "arguments": [{ "sharedId": shared_id }],
+        "awaitPromise": false,
+        "target": script::context_target(context, sandbox),
+    });
+    let result = command::send(session, "script.callFunction", params)
+        .await
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
+    if result.get("type").and_then(Value::as_str) == Some("exception") {
+        return Err(script::exception_error(&result));
+    }
+    let values = result.get("result").map(remote_value::to_json).unwrap_or_default();
+    let number = |index: usize| values.get(index).and_then(Value::as_f64).unwrap_or_default();
+    Ok([number(0), number(1), number(2), number(3)])
+}
+
+/// Decodes the base64 data of a screenshot.
+pub fn decode(data: &str) -> Result<Vec<u8>, BrowserError> {
+    BASE64_STANDARD
+        .decode(data)
+        .map_err(|e| BrowserError::Screenshot(format!("Decoding the screenshot failed: {}", e)))
+}
+
+/// Returns the options with the format inferred from the extension of `path`, keeping
+/// the quality of `options` when its format matches.
+pub fn options_for_path(path: &Path, options: &ScreenshotOptions) -> Result<ScreenshotOptions, BrowserError> {
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        BrowserError::Screenshot(format!(
+            "Unsupported screenshot file extension, expected png, jpg, jpeg or webp: {}",
+            path.display()
+        ))
+    })?;
+    let format = match (format, options.format) {
+        (ImageFormat::Jpeg { .. }, ImageFormat::Jpeg { quality }) => ImageFormat::Jpeg { quality },
+        (ImageFormat::Webp { .. }, ImageFormat::Webp { quality }) => ImageFormat::Webp { quality },
+        (format, _) => format,
+    };
+    Ok(options.clone().format(format))
+}
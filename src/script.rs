@@ -0,0 +1,126 @@
+use log::debug;
+use serde_json::{Value, json};
+use webdriverbidi::model::script::ExceptionDetails;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::command;
+use crate::error::BrowserError;
+use crate::remote_value;
+
+// --------------------------------------------------
+
+/// The sandbox realm autom8's own scripts run in by default, so that pages
+/// overriding globals such as `document.querySelector` can't break them.
+pub const DEFAULT_SANDBOX: &str = "autom8";
+
+// --------------------------------------------------
+
+/// Returns the `script.ContextTarget` for a context and, optionally, a sandbox.
+pub fn context_target(context: &str, sandbox: Option<&str>) -> Value {
+    match sandbox {
+        Some(sandbox) => json!({ "context": context, "sandbox": sandbox }),
+        None => json!({ "context": context }),
+    }
+}
+
+/// Returns the `ScriptException` error of a raw `script.EvaluateResult` exception.
+pub fn exception_error(result: &Value) -> BrowserError {
+    let details = result.get("exceptionDetails").unwrap_or(&Value::Null);
+    let frames = details
+        .pointer("/stackTrace/callFrames")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|frame| {
+            let text = |key: &str| frame.get(key).and_then(Value::as_str).unwrap_or_default();
+            let number = |key: &str| frame.get(key).and_then(Value::as_u64).unwrap_or_default();
+            stack_frame(text("functionName"), text("url"), number("lineNumber"), number("columnNumber"))
+        });
+    BrowserError::ScriptException {
+        message: details.get("text").and_then(Value::as_str).unwrap_or_default().to_string(),
+        stack: stack_trace(frames),
+    }
+}
+
+/// Returns the `ScriptException` error of typed exception details.
+pub fn exception_details_error(details: &ExceptionDetails) -> BrowserError {
+    let frames = details.stack_trace.call_frames.iter().map(|frame| {
+        stack_frame(&frame.function_name, &frame.url, frame.line_number, frame.column_number)
+    });
+    BrowserError::ScriptException {
+        message: details.text.clone(),
+        stack: stack_trace(frames),
+    }
+}
+
+/// Formats a stack frame like JavaScript engines do, with 1-based line and column numbers.
+fn stack_frame(function_name: &str, url: &str, line_number: u64, column_number: u64) -> String {
+    let function_name = if function_name.is_empty() { "<anonymous>" } else { function_name };
+    format!("at {} ({}:{}:{})", function_name, url, line_number + 1, column_number + 1)
+}
+
+fn stack_trace(frames: impl Iterator<Item = String>) -> Option<String> {
+    let stack = frames.collect::<Vec<_>>().join("\n");
+    (!stack.is_empty()).then_some(stack)
+}
+
+/// Returns the `script.RealmTarget` for a realm.
+pub fn realm_target(realm: &str) -> Value {
+    json!({ "realm": realm })
+}
+
+/// Evaluates an expression, awaiting it if it's a promise, and returns its value as JSON.
+pub async fn evaluate(
+    session: &mut WebDriverBiDiSession,
+    target: Value,
+    expression: &str,
+) -> Result<Value, BrowserError> {
+    debug!("Evaluating script in {}", target);
+    let params = json!({
+        "expression": expression,
+        "target": target,
+        "awaitPromise": true,
+    });
+    let result = command::send(session, "script.evaluate", params)
+        .await
+        .map_err(|e| BrowserError::command("script.evaluate", e))?;
+    evaluate_result_to_json(&result)
+}
+
+/// Calls a function with JSON arguments, awaiting the result if it's a promise,
+/// and returns it as JSON.
+pub async fn call_function(
+    session: &mut WebDriverBiDiSession,
+    target: Value,
+    function_declaration: &str,
+    arguments: &[Value],
+) -> Result<Value, BrowserError> {
+    debug!("Calling function in {}", target);
+    let params = json!({
+        "functionDeclaration": function_declaration,
+        "arguments": arguments.iter().map(remote_value::from_json).collect::<Vec<_>>(),
+        "target": target,
+        "awaitPromise": true,
+    });
+    let result = command::send(session, "script.callFunction", params)
+        .await
+        .map_err(|e| BrowserError::command("script.callFunction", e))?;
+    evaluate_result_to_json(&result)
+}
+
+/// Converts a raw `script.EvaluateResult` into the JSON value of its result.
+fn evaluate_result_to_json(result: &Value) -> Result<Value, BrowserError> {
+    match result.get("type").and_then(Value::as_str) {
+        Some("success") => Ok(result
+            .get("result")
+            .map(remote_value::to_json)
+            .unwrap_or(Value::Null)),
+        Some("exception") => Err(exception_error(result)),
+        _ => Err(BrowserError::JavaScript(
+            "Unexpected result from script evaluation".to_string(),
+        )),
+    }
+}
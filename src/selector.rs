@@ -0,0 +1,55 @@
+use serde_json::Value;
+
+// --------------------------------------------------
+
+/// JavaScript function resolving a locator to the first matching element, or `null`.
+///
+/// The locator is split on `>>>`, e.g. `my-app >>> settings-panel >>> button.save`:
+/// the first segment is matched against the document, and each following segment is
+/// searched for inside the shadow root of the previous match, descending through nested
+/// open shadow roots. A leading `>>>` searches the whole document, including every open
+/// shadow root.
+pub const QUERY_SELECTOR_FN: &str = r#"(selector) => {
+    const deepQuery = (root, css) => {
+        const found = root.querySelector(css);
+        if (found) {
+            return found;
+        }
+        for (const element of root.querySelectorAll("*")) {
+            if (element.shadowRoot) {
+                const nested = deepQuery(element.shadowRoot, css);
+                if (nested) {
+                    return nested;
+                }
+            }
+        }
+        return null;
+    };
+    const segments = selector.split(">>>").map((s) => s.trim());
+    let element = null;
+    for (const [i, segment] of segments.entries()) {
+        if (i === 0) {
+            if (segment !== "") {
+                element = document.querySelector(segment);
+                if (!element) {
+                    return null;
+                }
+            }
+            continue;
+        }
+        const root = element ? (element.shadowRoot || element) : document;
+        element = deepQuery(root, segment);
+        if (!element) {
+            return null;
+        }
+    }
+    return element;
+}"#;
+
+// --------------------------------------------------
+
+/// Returns a JavaScript expression evaluating to the element matched by the selector,
+/// or `null` if there is none. Shadow-piercing selectors are supported.
+pub fn query_selector(selector: &str) -> String {
+    format!("({})({})", QUERY_SELECTOR_FN, Value::from(selector))
+}
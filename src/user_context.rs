@@ -0,0 +1,96 @@
+use log::debug;
+use webdriverbidi::model::browser::{CreateUserContextParameters, RemoveUserContextParameters};
+use webdriverbidi::model::browsing_context::{CloseParameters, CreateParameters, CreateType, GetTreeParameters};
+use webdriverbidi::model::common::EmptyParams;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::emulation::DEFAULT_USER_CONTEXT;
+use crate::error::BrowserError;
+
+// --------------------------------------------------
+
+/// Creates a user context: an isolated profile whose pages share no cookies,
+/// storage or cache with the pages of other user contexts.
+pub async fn create_user_context(session: &mut WebDriverBiDiSession) -> Result<String, BrowserError> {
+    let params = CreateUserContextParameters::new(None, None, None);
+    let result = session
+        .browser_create_user_context(params)
+        .await
+        .map_err(|e| BrowserError::command("browser.createUserContext", e))?;
+    debug!("User context created: {}", result.user_context);
+    Ok(result.user_context)
+}
+
+/// Returns the ids of the user contexts, starting with `"default"`.
+pub async fn get_user_contexts(session: &mut WebDriverBiDiSession) -> Result<Vec<String>, BrowserError> {
+    let result = session
+        .browser_get_user_contexts(EmptyParams::new())
+        .await
+        .map_err(|e| BrowserError::command("browser.getUserContexts", e))?;
+    Ok(result
+        .user_contexts
+        .into_iter()
+        .map(|info| info.user_context)
+        .collect())
+}
+
+/// Removes a user context, closing all of its pages without running `beforeunload` handlers.
+pub async fn remove_user_context(
+    session: &mut WebDriverBiDiSession,
+    user_context: &str,
+) -> Result<(), BrowserError> {
+    if user_context == DEFAULT_USER_CONTEXT {
+        return Err(BrowserError::UserContext(
+            "The default user context can't be removed".to_string(),
+        ));
+    }
+    let params = RemoveUserContextParameters::new(user_context.to_string());
+    session
+        .browser_remove_user_context(params)
+        .await
+        .map_err(|e| BrowserError::command("browser.removeUserContext", e))?;
+    debug!("User context removed: {}", user_context);
+    Ok(())
+}
+
+// --------------------------------------------------
+
+/// Opens a new tab in a user context, or in the default one, and returns its browsing context.
+pub async fn create_page(
+    session: &mut WebDriverBiDiSession,
+    user_context: Option<&str>,
+) -> Result<String, BrowserError> {
+    let params = CreateParameters::new(
+        CreateType::Tab,
+        None,
+        None,
+        user_context.map(str::to_string),
+    );
+    let result = session.browsing_context_create(params).await.map_err(|e| BrowserError::command("browsingContext.create", e))?;
+    debug!("Page created: {}", result.context);
+    Ok(result.context)
+}
+
+/// Closes a page without running its `beforeunload` handlers.
+pub async fn close_page(session: &mut WebDriverBiDiSession, context: &str) -> Result<(), BrowserError> {
+    let params = CloseParameters::new(context.to_string(), None);
+    session.browsing_context_close(params).await.map_err(|e| BrowserError::command("browsingContext.close", e))?;
+    Ok(())
+}
+
+/// Returns the user context of a top-level browsing context.
+pub async fn user_context_of(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+) -> Result<String, BrowserError> {
+    let params = GetTreeParameters::new(Some(0), Some(context.to_string()));
+    let result = session.browsing_context_get_tree(params).await.map_err(|e| BrowserError::command("browsingContext.getTree", e))?;
+    Ok(result
+        .contexts
+        .into_iter()
+        .next()
+        .map(|info| info.user_context)
+        .unwrap_or_else(|| DEFAULT_USER_CONTEXT.to_string()))
+}
@@ -0,0 +1,437 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::prelude::*;
+use log::{debug, info};
+use serde_json::Value;
+use webdriverbidi::session::WebDriverBiDiSession;
+
+// --------------------------------------------------
+
+use crate::error::BrowserError;
+use crate::screenshot::{self, ImageFormat, ScreenshotOptions};
+use crate::{files, frame, script, selector};
+
+// --------------------------------------------------
+
+/// Set this environment variable (to anything but `0` or `false`) to overwrite the
+/// baselines with the current screenshots instead of comparing them.
+pub const UPDATE_BASELINES_ENV: &str = "AUTOM8_UPDATE_BASELINES";
+
+const MASK_ATTRIBUTE: &str = "data-autom8-mask";
+
+/// The sandbox realm the screenshots are compared in, out of reach of the page scripts.
+const COMPARE_SANDBOX: &str = "autom8-visual";
+
+/// Covers every element matching each selector with an opaque overlay. The last segment
+/// of a shadow-piercing selector is matched in the shadow root of the first match of the
+/// previous ones, or in the whole document when there are none.
+fn add_masks_fn() -> String {
+    format!(
+        r##"(selectors) => {{
+    const query = {};
+    const deepQueryAll = (root, css) => {{
+        const found = Array.from(root.querySelectorAll(css));
+        for (const element of root.querySelectorAll("*")) {{
+            if (element.shadowRoot) {{
+                found.push(...deepQueryAll(element.shadowRoot, css));
+            }}
+        }}
+        return found;
+    }};
+    const queryAll = (selector) => {{
+        const separator = selector.lastIndexOf(">>>");
+        if (separator < 0) {{
+            return Array.from(document.querySelectorAll(selector));
+        }}
+        const host = selector.slice(0, separator).trim();
+        const css = selector.slice(separator + 3).trim();
+        if (host === "") {{
+            return deepQueryAll(document, css);
+        }}
+        const element = query(host);
+        return element ? deepQueryAll(element.shadowRoot || element, css) : [];
+    }};
+    for (const element of selectors.flatMap(queryAll)) {{
+        const rect = element.getBoundingClientRect();
+        const overlay = document.createElement("div");
+        overlay.setAttribute("{}", "");
+        Object.assign(overlay.style, {{
+            position: "absolute",
+            left: `${{rect.left + window.scrollX}}px`,
+            top: `${{rect.top + window.scrollY}}px`,
+            width: `${{rect.width}}px`,
+            height: `${{rect.height}}px`,
+            margin: "0",
+            background: "#ff00ff",
+            zIndex: "2147483647",
+            pointerEvents: "none",
+        }});
+        document.documentElement.appendChild(overlay);
+    }}
+}}"##,
+        selector::QUERY_SELECTOR_FN,
+        MASK_ATTRIBUTE
+    )
+}
+
+fn remove_masks_fn() -> String {
+    format!(
+        r#"() => document.querySelectorAll("[{}]").forEach((overlay) => overlay.remove())"#,
+        MASK_ATTRIBUTE
+    )
+}
+
+/// Decodes two base64 PNG images and counts the pixels whose largest channel difference
+/// exceeds `threshold` (0..1), rendering the differing pixels in red on a faded copy.
+/// Runs in `COMPARE_SANDBOX`, so page scripts can't alter the decoding or the verdict.
+const COMPARE_FN: &str = r#"async (expected, actual, threshold) => {
+    const decode = async (data) => {
+        const bytes = Uint8Array.from(atob(data), (c) => c.charCodeAt(0));
+        const bitmap = await createImageBitmap(new Blob([bytes], { type: "image/png" }));
+        const canvas = new OffscreenCanvas(bitmap.width, bitmap.height);
+        const context = canvas.getContext("2d");
+        context.drawImage(bitmap, 0, 0);
+        return context.getImageData(0, 0, bitmap.width, bitmap.height);
+    };
+    const [a, b] = await Promise.all([decode(expected), decode(actual)]);
+    if (a.width !== b.width || a.height !== b.height) {
+        return {
+            sizeMismatch: true,
+            expectedSize: [a.width, a.height],
+            actualSize: [b.width, b.height],
+        };
+    }
+    const diff = new ImageData(a.width, a.height);
+    const limit = threshold * 255;
+    let differing = 0;
+    for (let i = 0; i < a.data.length; i += 4) {
+        let distance = 0;
+        for (let c = 0; c < 4; c++) {
+            distance = Math.max(distance, Math.abs(a.data[i + c] - b.data[i + c]));
+        }
+        if (distance > limit) {
+            differing++;
+            diff.data.set([255, 0, 0, 255], i);
+        } else {
+            const gray = (b.data[i] + b.data[i + 1] + b.data[i + 2]) / 3;
+            const faded = 255 - (255 - gray) * 0.2;
+            diff.data.set([faded, faded, faded, 255], i);
+        }
+    }
+    let diffData = null;
+    if (differing > 0) {
+        const canvas = new OffscreenCanvas(a.width, a.height);
+        canvas.getContext("2d").putImageData(diff, 0, 0);
+        const blob = await canvas.convertToBlob({ type: "image/png" });
+        const bytes = new Uint8Array(await blob.arrayBuffer());
+        let binary = "";
+        for (let i = 0; i < bytes.length; i += 0x8000) {
+            binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+        }
+        diffData = btoa(binary);
+    }
+    return { sizeMismatch: false, differing, total: a.width * a.height, diff: diffData };
+}"#;
+
+// --------------------------------------------------
+
+/// Options for comparing a screenshot against a baseline image.
+///
+/// Baselines are stored as `<baseline_dir>/<name>.png`. When a comparison fails, the
+/// actual screenshot and an image highlighting the differing pixels in red are written
+/// to `<output_dir>/<name>-actual.png` and `<output_dir>/<name>-diff.png`.
+///
+/// # Example
+/// ```rust,no_run
+/// # use autom8::{ScreenshotMatchOptions, ScreenshotOptions};
+/// # async fn example(browser: &autom8::Browser) -> Result<(), Box<dyn std::error::Error>> {
+/// let options = ScreenshotMatchOptions::new()
+///     .screenshot(ScreenshotOptions::new().viewport())
+///     .threshold(0.2)
+///     .max_diff_ratio(0.001)
+///     .mask(".ad-banner")
+///     .mask("#current-time");
+/// browser.assert_screenshot_matches("home", &options).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenshotMatchOptions {
+    screenshot: ScreenshotOptions,
+    threshold: f64,
+    max_diff_ratio: f64,
+    masks: Vec<String>,
+    baseline_dir: PathBuf,
+    output_dir: PathBuf,
+}
+
+impl Default for ScreenshotMatchOptions {
+    fn default() -> Self {
+        Self {
+            screenshot: ScreenshotOptions::default(),
+            threshold: 0.1,
+            max_diff_ratio: 0.0,
+            masks: Vec::new(),
+            baseline_dir: PathBuf::from("tests/screenshots"),
+            output_dir: PathBuf::from("target/screenshot-diffs"),
+        }
+    }
+}
+
+impl ScreenshotMatchOptions {
+    /// Creates the default options: a full page screenshot, a per-pixel threshold of `0.1`,
+    /// no differing pixels allowed, baselines in `tests/screenshots` and failure images
+    /// in `target/screenshot-diffs`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the origin and clip of the screenshot. Screenshots are always compared as PNG.
+    pub fn screenshot(mut self, options: ScreenshotOptions) -> Self {
+        self.screenshot = options;
+        self
+    }
+
+    /// Sets how much a pixel may differ before it counts as differing, from `0.0`
+    /// (any difference) to `1.0` (none), as a fraction of the largest channel difference.
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Sets the largest ratio of differing pixels, from `0.0` to `1.0`, for the comparison to pass.
+    pub fn max_diff_ratio(mut self, max_diff_ratio: f64) -> Self {
+        self.max_diff_ratio = max_diff_ratio;
+        self
+    }
+
+    /// Covers every element matching a selector with an opaque box before capturing,
+    /// e.g. to hide dynamic content such as dates or ads. The selector may pierce frames
+    /// with `|>`, e.g. `"#ads |> .banner"`.
+    pub fn mask(mut self, selector: &str) -> Self {
+        self.masks.push(selector.to_string());
+        self
+    }
+
+    /// Sets the directory the baseline images are stored in.
+    pub fn baseline_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.baseline_dir = dir.into();
+        self
+    }
+
+    /// Sets the directory the actual and diff images of failed comparisons are written to.
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = dir.into();
+        self
+    }
+}
+
+// --------------------------------------------------
+
+/// Returns whether the baselines should be updated instead of compared.
+fn update_baselines() -> bool {
+    is_enabled(env::var(UPDATE_BASELINES_ENV).ok().as_deref())
+}
+
+/// Returns whether a flag variable is set to anything but empty, `0` or `false`.
+fn is_enabled(value: Option<&str>) -> bool {
+    value.is_some_and(|value| !value.is_empty() && value != "0" && !value.eq_ignore_ascii_case("false"))
+}
+
+/// The verdict on the result of `COMPARE_FN`.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    SizeMismatch { expected: String, actual: String },
+    Matches,
+    Differs { differing: u64, ratio: f64 },
+}
+
+/// Reads the result of `COMPARE_FN`: the screenshots match when at most `max_diff_ratio`
+/// of the pixels differ.
+fn outcome(comparison: &Value, max_diff_ratio: f64) -> Outcome {
+    if comparison["sizeMismatch"].as_bool().unwrap_or(false) {
+        return Outcome::SizeMismatch {
+            expected: size_to_string(&comparison["expectedSize"]),
+            actual: size_to_string(&comparison["actualSize"]),
+        };
+    }
+    let differing = comparison["differing"].as_u64().unwrap_or(0);
+    let total = comparison["total"].as_u64().unwrap_or(0).max(1);
+    let ratio = differing as f64 / total as f64;
+    debug!("{} of {} pixels differ from the baseline", differing, total);
+    if ratio <= max_diff_ratio {
+        Outcome::Matches
+    } else {
+        Outcome::Differs { differing, ratio }
+    }
+}
+
+/// Captures a PNG screenshot with the masks applied.
+async fn capture(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    options: &ScreenshotMatchOptions,
+) -> Result<Vec<u8>, BrowserError> {
+    // The element selectors of the masks, grouped by the browsing context of their frame
+    let mut masks: Vec<(String, Vec<String>)> = Vec::new();
+    for mask in &options.masks {
+        let (mask_context, selector) = frame::resolve_locator(session, context, sandbox, mask)
+            .await
+            .map_err(|e| e.for_locator(mask))?;
+        match masks.iter_mut().find(|(ctx, _)| *ctx == mask_context) {
+            Some((_, selectors)) => selectors.push(selector),
+            None => masks.push((mask_context, vec![selector])),
+        }
+    }
+
+    for (mask_context, selectors) in &masks {
+        let target = script::context_target(mask_context, sandbox);
+        script::call_function(session, target, &add_masks_fn(), &[Value::from(selectors.clone())]).await?;
+    }
+    let screenshot_options = options.screenshot.clone().format(ImageFormat::Png);
+    let data =
+        screenshot::take_screenshot(session, context.to_string(), sandbox, &screenshot_options)
+            .await;
+    for (mask_context, _) in &masks {
+        let target = script::context_target(mask_context, sandbox);
+        script::call_function(session, target, &remove_masks_fn(), &[]).await?;
+    }
+    screenshot::decode(&data?)
+}
+
+/// Compares a screenshot of the context against the baseline `name`, or overwrites the
+/// baseline when `AUTOM8_UPDATE_BASELINES` is set.
+pub async fn assert_screenshot_matches(
+    session: &mut WebDriverBiDiSession,
+    context: &str,
+    sandbox: Option<&str>,
+    name: &str,
+    options: &ScreenshotMatchOptions,
+) -> Result<(), BrowserError> {
+    let name = name.strip_suffix(".png").unwrap_or(name);
+    let baseline_path = options.baseline_dir.join(format!("{}.png", name));
+    let actual = capture(session, context, sandbox, options).await?;
+
+    if update_baselines() {
+        files::write_file(&baseline_path, &actual, BrowserError::Screenshot)?;
+        info!("Baseline updated: {}", baseline_path.display());
+        return Ok(());
+    }
+
+    let actual_path = options.output_dir.join(format!("{}-actual.png", name));
+    if !baseline_path.exists() {
+        files::write_file(&actual_path, &actual, BrowserError::Screenshot)?;
+        return Err(BrowserError::Assertion(format!(
+            "No baseline at {}, the actual screenshot was written to {}. Set {}=1 to create the baseline",
+            baseline_path.display(),
+            actual_path.display(),
+            UPDATE_BASELINES_ENV
+        )));
+    }
+    let expected = read_baseline(&baseline_path)?;
+    if expected == actual {
+        debug!("Screenshot identical to baseline: {}", baseline_path.display());
+        return Ok(());
+    }
+
+    let arguments = [
+        Value::from(BASE64_STANDARD.encode(&expected)),
+        Value::from(BASE64_STANDARD.encode(&actual)),
+        Value::from(options.threshold),
+    ];
+    let target = script::context_target(context, Some(COMPARE_SANDBOX));
+    let comparison = script::call_function(session, target, COMPARE_FN, &arguments).await?;
+
+    let (differing, ratio) = match outcome(&comparison, options.max_diff_ratio) {
+        Outcome::Matches => return Ok(()),
+        Outcome::Differs { differing, ratio } => (differing, ratio),
+        Outcome::SizeMismatch { expected, actual: actual_size } => {
+            files::write_file(&actual_path, &actual, BrowserError::Screenshot)?;
+            return Err(BrowserError::Assertion(format!(
+                "Screenshot size {} differs from baseline size {} ({}), the actual screenshot was written to {}",
+                actual_size,
+                expected,
+                baseline_path.display(),
+                actual_path.display()
+            )));
+        }
+    };
+
+    files::write_file(&actual_path, &actual, BrowserError::Screenshot)?;
+    let diff_path = options.output_dir.join(format!("{}-diff.png", name));
+    if let Some(diff) = comparison["diff"].as_str() {
+        files::write_file(&diff_path, &screenshot::decode(diff)?, BrowserError::Screenshot)?;
+    }
+    Err(BrowserError::Assertion(format!(
+        "{} pixels ({:.4}%) differ from baseline {}, more than the allowed {:.4}%. See {} and {}",
+        differing,
+        ratio * 100.0,
+        baseline_path.display(),
+        options.max_diff_ratio * 100.0,
+        actual_path.display(),
+        diff_path.display()
+    )))
+}
+
+fn read_baseline(path: &Path) -> Result<Vec<u8>, BrowserError> {
+    fs::read(path).map_err(|e| {
+        BrowserError::Screenshot(format!("Reading the baseline {} failed: {}", path.display(), e))
+    })
+}
+
+fn size_to_string(size: &Value) -> String {
+    format!("{}x{}", size[0], size[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn update_flag_is_enabled_by_any_other_value() {
+        assert!(!is_enabled(None));
+        assert!(!is_enabled(Some("")));
+        assert!(!is_enabled(Some("0")));
+        assert!(!is_enabled(Some("FALSE")));
+        assert!(is_enabled(Some("1")));
+        assert!(is_enabled(Some("yes")));
+    }
+
+    #[test]
+    fn size_mismatch_reports_both_sizes() {
+        let comparison = json!({ "sizeMismatch": true, "expectedSize": [800, 600], "actualSize": [800, 601] });
+        assert_eq!(
+            outcome(&comparison, 1.0),
+            Outcome::SizeMismatch {
+                expected: "800x600".to_string(),
+                actual: "800x601".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn differing_pixels_up_to_the_ratio_match() {
+        let comparison = json!({ "sizeMismatch": false, "differing": 5, "total": 1000 });
+        assert_eq!(outcome(&comparison, 0.005), Outcome::Matches);
+        assert_eq!(outcome(&comparison, 0.004), Outcome::Differs { differing: 5, ratio: 0.005 });
+    }
+
+    #[test]
+    fn no_differing_pixel_matches_without_tolerance() {
+        let comparison = json!({ "sizeMismatch": false, "differing": 0, "total": 1000 });
+        assert_eq!(outcome(&comparison, 0.0), Outcome::Matches);
+        let comparison = json!({ "sizeMismatch": false, "differing": 1, "total": 1000 });
+        assert_eq!(outcome(&comparison, 0.0), Outcome::Differs { differing: 1, ratio: 0.001 });
+    }
+
+    #[test]
+    fn empty_image_doesnt_divide_by_zero() {
+        let comparison = json!({ "sizeMismatch": false, "differing": 0, "total": 0 });
+        assert_eq!(outcome(&comparison, 0.0), Outcome::Matches);
+    }
+}